@@ -0,0 +1,268 @@
+use fxhash::FxHashSet as HashSet;
+use crate::activity::{self, Activity};
+use crate::ages::{self, CellAges};
+use crate::state::*;
+use crate::rule::Rule;
+use crate::topology::Topology;
+#[cfg(feature = "gui")]
+use nannou::prelude::geom::Tri;
+use rand::Rng;
+#[cfg(feature = "gui")]
+use std::collections::LinkedList;
+
+// A hash set of live cells, like SingleState, that only looks at the cells
+// next to the ones that changed in the last tick. A cell can only change if
+// something next to it did, so still lifes cost nothing to tick, and the time
+// each tick takes follows the activity rather than the population.
+pub struct ActiveState {
+    cells: HashSet<Cell>,
+    kill_list: Vec<Cell>,
+    res_list: Vec<Cell>,
+    // Cells changed from outside of a tick since the last one.
+    edited: HashSet<Cell>,
+    generation: usize,
+    rule: Rule,
+    topology: Topology,
+    ages: Option<CellAges>,
+    activity: Option<Activity>,
+}
+
+pub fn active_state() -> ActiveState {
+    ActiveState {
+        cells: HashSet::default(),
+        kill_list: Vec::new(),
+        res_list: Vec::new(),
+        edited: HashSet::default(),
+        generation: 0,
+        rule: Rule::default(),
+        topology: Topology::default(),
+        ages: None,
+        activity: None,
+    }
+}
+
+impl ActiveState {
+    // Cells that may change in the next tick: the ones that changed in the
+    // last tick or were edited since, along with their neighbors.
+    fn candidates(&self) -> HashSet<Cell> {
+        let mut candidates = HashSet::default();
+
+        let changed = self.kill_list.iter().chain(self.res_list.iter()).chain(self.edited.iter());
+        for cell in changed {
+            candidates.insert(*cell);
+            candidates.extend(get_neighbors(cell, self.topology));
+        }
+
+        candidates
+    }
+}
+
+impl State for ActiveState {
+    fn tick(&mut self) {
+        if !self.cells.is_empty() {
+            self.generation += 1;
+        }
+
+        let candidates = self.candidates();
+
+        // The lists are kept around after ticking, as the changes of the tick,
+        // and to know where to look in the next one.
+        self.kill_list.clear();
+        self.res_list.clear();
+        self.edited.clear();
+
+        let topology = self.topology;
+        for cell in candidates.iter() {
+            let neighbors: [Cell; 8] = get_neighbors(cell, topology);
+            let neighbor_count = count_living_neighbors(&neighbors, &self.cells);
+            match self.cells.contains(cell) {
+                true if !self.rule.survives(neighbor_count) => self.kill_list.push(*cell),
+                false if self.rule.born(neighbor_count) && topology.contains(cell) => self.res_list.push(*cell),
+                _ => (),
+            }
+        }
+
+        for cell in self.kill_list.iter() {
+            self.cells.remove(cell);
+        }
+        for cell in self.res_list.iter() {
+            self.cells.insert(*cell);
+        }
+
+        if let Some(ages) = self.ages.as_mut() {
+            ages.update(self.generation, &self.res_list, &self.kill_list);
+        }
+        if let Some(activity) = self.activity.as_mut() {
+            activity.update(&self.res_list, &self.kill_list);
+        }
+    }
+
+    fn last_changes(&self) -> Option<Changes<'_>> {
+        Some(Changes {
+            births: &self.res_list,
+            deaths: &self.kill_list,
+        })
+    }
+
+    fn insert_cells(&mut self, mut collection: Vec<Cell>) {
+        for cell in collection.drain(0..) {
+            self.insert_cell(cell);
+        }
+    }
+
+    fn insert_cells_rel(&mut self, mut collection: Vec<Cell>, view: (f64, f64)) {
+        for cell in collection.drain(0..) {
+            self.insert_cell(relative_to_view(cell, view));
+        }
+    }
+
+    fn insert_cell(&mut self, cell: Cell) {
+        if let Some(cell) = self.topology.wrap(cell)
+            && self.cells.insert(cell)
+        {
+            self.edited.insert(cell);
+            if let Some(ages) = self.ages.as_mut() {
+                ages.insert(self.generation, cell);
+            }
+        }
+    }
+
+    fn remove_cell(&mut self, cell: Cell) {
+        if let Some(cell) = self.topology.wrap(cell)
+            && self.cells.remove(&cell)
+        {
+            self.edited.insert(cell);
+            if let Some(ages) = self.ages.as_mut() {
+                ages.remove(&cell);
+            }
+        }
+    }
+
+    fn toggle_cell(&mut self, cell: Cell) {
+        if let Some(cell) = self.topology.wrap(cell) {
+            match self.cells.contains(&cell) {
+                true => self.remove_cell(cell),
+                _ => self.insert_cell(cell),
+            }
+        }
+    }
+
+    fn is_alive(&self, cell: Cell) -> bool {
+        self.topology.wrap(cell).is_some_and(|cell| self.cells.contains(&cell))
+    }
+
+    fn remove_cells(&mut self, mut collection: Vec<Cell>) {
+        for cell in collection.drain(0..) {
+            self.remove_cell(cell);
+        }
+    }
+
+    fn collect_cells(&self) -> Vec<Cell> {
+        self.cells.iter().copied().collect()
+    }
+
+    fn cells_in_rect(&self, min: Cell, max: Cell) -> Vec<Cell> {
+        self.cells
+            .iter()
+            .filter(|cell| in_rect(cell, min, max))
+            .copied()
+            .collect()
+    }
+
+    fn count_cells(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn random_cell(&self) -> Cell {
+        let random_index = rand::thread_rng().gen_range(0..self.cells.len());
+        *(self.cells.iter().nth(random_index).unwrap())
+    }
+
+    fn generation(&self) -> usize {
+        self.generation
+    }
+
+    fn set_generation(&mut self, generation: usize) {
+        self.generation = generation;
+    }
+
+    fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    // Under another rule any cell may change, so every one is looked at in
+    // the next tick.
+    fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+        self.edited.extend(self.cells.iter().copied());
+    }
+
+    fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    fn set_topology(&mut self, topology: Topology) -> Result<(), String> {
+        let collection = self.collect_cells();
+        self.topology = topology;
+        self.cells.clear();
+        self.kill_list.clear();
+        self.res_list.clear();
+        self.edited.clear();
+        if let Some(ages) = self.ages.as_mut() {
+            ages.clear();
+        }
+        if let Some(activity) = self.activity.as_mut() {
+            activity.clear();
+        }
+        self.insert_cells(collection);
+
+        Ok(())
+    }
+
+    fn set_age_tracking(&mut self, enabled: bool) {
+        self.ages = match enabled {
+            true => {
+                let mut ages = ages::cell_ages();
+                for cell in self.cells.iter() {
+                    ages.insert(self.generation, *cell);
+                }
+                Some(ages)
+            }
+            _ => None,
+        };
+    }
+
+    fn ages(&self) -> Option<&CellAges> {
+        self.ages.as_ref()
+    }
+
+    fn set_activity_tracking(&mut self, enabled: bool) {
+        self.activity = enabled.then(activity::activity);
+    }
+
+    fn activity(&self) -> Option<&Activity> {
+        self.activity.as_ref()
+    }
+
+    #[cfg(feature = "gui")]
+    fn get_tris(
+        &self,
+        view: (f64, f64),
+        cell_color: nannou::prelude::rgb::Rgb,
+        screen_left: i32,
+        screen_right: i32,
+        screen_top: i32,
+        screen_bottom: i32
+    ) -> LinkedList<Tri<([f32; 3], nannou::prelude::rgb::Rgb)>> {
+        let mut tri_list = LinkedList::default();
+
+        for cell in self.cells.iter().filter(|cell| cell.0 > screen_left && cell.0 < screen_right && cell.1 > screen_bottom && cell.1 < screen_top) {
+            let [first_tri, second_tri] = cell_tris(cell, view, cell_color);
+
+            tri_list.push_front(first_tri);
+            tri_list.push_front(second_tri);
+        }
+
+        tri_list
+    }
+}
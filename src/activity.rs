@@ -0,0 +1,127 @@
+use fxhash::FxHashMap as HashMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use crate::recording::Color;
+use crate::state::Cell;
+
+// How many times each cell has been born or died, since counting started.
+pub struct Activity {
+    changes: HashMap<Cell, u32>,
+}
+
+pub fn activity() -> Activity {
+    Activity {
+        changes: HashMap::default(),
+    }
+}
+
+// Colors of activity, from the least to the most active cells.
+const HEAT_COLORS: [Color; 4] = [[60, 0, 0], [200, 30, 0], [255, 200, 0], [255, 255, 255]];
+
+// Returns the color of a cell that changed the amount of times, where the
+// most active cell changed max_changes times.
+pub fn heat_color(changes: u32, max_changes: u32) -> Color {
+    // Spread out logarithmically, so a few very busy cells do not leave the
+    // rest of the map dark.
+    let position = ((1 + changes) as f32).ln() / ((1 + max_changes.max(1)) as f32).ln();
+    let position = position.min(1.0) * (HEAT_COLORS.len() - 1) as f32;
+    let index = (position as usize).min(HEAT_COLORS.len() - 2);
+    let fraction = position - index as f32;
+
+    let (from, to) = (HEAT_COLORS[index], HEAT_COLORS[index + 1]);
+    let mut color = [0; 3];
+    for (channel, value) in color.iter_mut().enumerate() {
+        *value = (from[channel] as f32 + (to[channel] as f32 - from[channel] as f32) * fraction) as u8;
+    }
+
+    color
+}
+
+impl Activity {
+    // Takes in the changes of a tick.
+    pub fn update(&mut self, births: &[Cell], deaths: &[Cell]) {
+        for cell in births.iter().chain(deaths.iter()) {
+            *self.changes.entry(*cell).or_insert(0) += 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.changes.clear();
+    }
+
+    // Cells that have changed, along with how many times.
+    pub fn iter(&self) -> impl Iterator<Item = (&Cell, &u32)> {
+        self.changes.iter()
+    }
+
+    pub fn max_changes(&self) -> u32 {
+        self.changes.values().copied().max().unwrap_or(0)
+    }
+
+    // Writes the heat map to a PNG if the path ends with ".png", and to CSV
+    // otherwise, with a line for each cell that has changed.
+    pub fn write(&self, path: &str, cell_size: u32) -> Result<(), String> {
+        let result = match path.to_lowercase().ends_with(".png") {
+            true => self.write_png(path, cell_size),
+            _ => self.write_csv(path),
+        };
+
+        result.map_err(|error| format!("could not write '{}': {}", path, error))
+    }
+
+    fn write_csv(&self, path: &str) -> Result<(), String> {
+        let mut cells: Vec<(&Cell, &u32)> = self.changes.iter().collect();
+        cells.sort_unstable();
+
+        let mut csv = String::from("x,y,changes\n");
+        for (cell, changes) in cells {
+            csv.push_str(&format!("{},{},{}\n", cell.0, cell.1, changes));
+        }
+
+        fs::write(path, csv).map_err(|error| error.to_string())
+    }
+
+    fn write_png(&self, path: &str, cell_size: u32) -> Result<(), String> {
+        // Without any changes, the image is a single cell of background.
+        let (left, right, bottom, top) = match self.changes.is_empty() {
+            true => (0, 0, 0, 0),
+            _ => (
+                self.changes.keys().map(|cell| cell.0).min().unwrap(),
+                self.changes.keys().map(|cell| cell.0).max().unwrap(),
+                self.changes.keys().map(|cell| cell.1).min().unwrap(),
+                self.changes.keys().map(|cell| cell.1).max().unwrap(),
+            ),
+        };
+
+        let cell_size = cell_size as usize;
+        let width = (right as i64 - left as i64 + 1) as usize * cell_size;
+        let height = (top as i64 - bottom as i64 + 1) as usize * cell_size;
+        if width > u32::MAX as usize || height > u32::MAX as usize || width * height > 1 << 30 {
+            return Err("the heat map is too big for an image, write it to CSV instead".to_string());
+        }
+
+        // Higher cells are shown higher up, like in the GUI.
+        let max_changes = self.max_changes();
+        let mut pixels = vec![0; width * height * 3];
+        for (cell, changes) in self.changes.iter() {
+            let color = heat_color(*changes, max_changes);
+            let x = (cell.0 as i64 - left as i64) as usize * cell_size;
+            let y = (top as i64 - cell.1 as i64) as usize * cell_size;
+            for row in y..y + cell_size {
+                for column in x..x + cell_size {
+                    let index = (row * width + column) * 3;
+                    pixels[index..index + 3].copy_from_slice(&color);
+                }
+            }
+        }
+
+        let file = File::create(path).map_err(|error| error.to_string())?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+        writer.write_image_data(&pixels).map_err(|error| error.to_string())?;
+        writer.finish().map_err(|error| error.to_string())
+    }
+}
@@ -0,0 +1,65 @@
+use fxhash::FxHashMap as HashMap;
+use crate::state::Cell;
+
+// Generations a dead cell is remembered for, to draw trails behind moving
+// cells.
+pub const TRAIL_LENGTH: usize = 20;
+
+// When each live cell was born, and when recently dead cells died.
+pub struct CellAges {
+    born: HashMap<Cell, usize>,
+    died: HashMap<Cell, usize>,
+}
+
+pub fn cell_ages() -> CellAges {
+    CellAges {
+        born: HashMap::default(),
+        died: HashMap::default(),
+    }
+}
+
+impl CellAges {
+    // Takes in the changes of a tick that led to the generation.
+    pub fn update(&mut self, generation: usize, births: &[Cell], deaths: &[Cell]) {
+        for cell in deaths {
+            self.born.remove(cell);
+            self.died.insert(*cell, generation);
+        }
+        for cell in births {
+            self.died.remove(cell);
+            self.born.insert(*cell, generation);
+        }
+
+        self.died.retain(|_, died| generation.saturating_sub(*died) < TRAIL_LENGTH);
+    }
+
+    // Cells given life from outside of a tick are newborn.
+    pub fn insert(&mut self, generation: usize, cell: Cell) {
+        self.died.remove(&cell);
+        self.born.entry(cell).or_insert(generation);
+    }
+
+    // Cells killed from outside of a tick leave no trail.
+    pub fn remove(&mut self, cell: &Cell) {
+        self.born.remove(cell);
+    }
+
+    pub fn clear(&mut self) {
+        self.born.clear();
+        self.died.clear();
+    }
+
+    pub fn age(&self, cell: &Cell, generation: usize) -> Option<usize> {
+        self.born.get(cell).map(|born| generation.saturating_sub(*born))
+    }
+
+    // Live cells, along with the generation they were born.
+    pub fn living(&self) -> impl Iterator<Item = (&Cell, &usize)> {
+        self.born.iter()
+    }
+
+    // Recently dead cells, along with the generation they died.
+    pub fn trail(&self) -> impl Iterator<Item = (&Cell, &usize)> {
+        self.died.iter()
+    }
+}
@@ -0,0 +1,170 @@
+use fxhash::FxHashMap as HashMap;
+use fxhash::FxHasher;
+use std::collections::VecDeque;
+use std::fmt;
+use std::hash::Hasher;
+use crate::state::{Cell, State};
+
+// How the cells behave over time, once they repeat themselves.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Behavior {
+    DiesOut { generation: usize },
+    Periodic { period: usize, displacement: Cell },
+}
+
+fn greatest_common_divisor(a: usize, b: usize) -> usize {
+    match b {
+        0 => a,
+        _ => greatest_common_divisor(b, a % b),
+    }
+}
+
+impl fmt::Display for Behavior {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Behavior::DiesOut { generation } => write!(f, "dies out at generation {}", generation),
+            Behavior::Periodic { period: 1, displacement: (0, 0) } => write!(f, "still life"),
+            Behavior::Periodic { period, displacement: (0, 0) } => write!(f, "oscillator, period {}", period),
+            Behavior::Periodic { period, displacement: (dx, dy) } => {
+                // Speeds are written as fractions of c, one cell per generation.
+                let distance = dx.unsigned_abs().max(dy.unsigned_abs()) as usize;
+                let divisor = greatest_common_divisor(distance, period);
+                let (distance, generations) = (distance / divisor, period / divisor);
+
+                let speed = match (distance, generations) {
+                    (1, 1) => "c".to_string(),
+                    (distance, 1) => format!("{}c", distance),
+                    (1, generations) => format!("c/{}", generations),
+                    (distance, generations) => format!("{}c/{}", distance, generations),
+                };
+                let direction = if dx == 0 || dy == 0 {
+                    "orthogonal"
+                } else if dx.abs() == dy.abs() {
+                    "diagonal"
+                } else {
+                    "oblique"
+                };
+
+                write!(f, "{} {} spaceship, period {}", speed, direction, period)
+            }
+        }
+    }
+}
+
+// Most cells kept of the shapes seen, after which the oldest shapes are
+// forgotten, even if they are within the longest period.
+const SHAPE_CELL_LIMIT: usize = 1 << 22;
+
+// A generation seen, by the hash of its shape.
+struct Seen {
+    generation: usize,
+    position: Cell,
+    // Compared on a matching hash, so that two shapes with the same hash are
+    // not taken for each other.
+    shape: Vec<Cell>,
+}
+
+// Spots when the cells repeat an earlier generation, anywhere in the universe.
+// Generations must be observed one after another.
+pub struct PeriodDetector {
+    seen: HashMap<u64, Seen>,
+    order: VecDeque<(u64, usize)>,
+    shape_cells: usize,
+    last_generation: Option<usize>,
+    max_period: usize,
+}
+
+pub fn period_detector(max_period: usize) -> PeriodDetector {
+    PeriodDetector {
+        seen: HashMap::default(),
+        order: VecDeque::new(),
+        shape_cells: 0,
+        last_generation: None,
+        max_period,
+    }
+}
+
+// Returns the shape of the cells, independent of where they are, along with
+// its hash. Also returns the lower left corner of the cells, which tells where
+// they are.
+fn shape_hash(cells: &[Cell]) -> (u64, Vec<Cell>, Cell) {
+    let left = cells.iter().map(|cell| cell.0).min().unwrap_or(0);
+    let bottom = cells.iter().map(|cell| cell.1).min().unwrap_or(0);
+
+    let mut shape: Vec<Cell> = cells
+        .iter()
+        .map(|cell| (cell.0.wrapping_sub(left), cell.1.wrapping_sub(bottom)))
+        .collect();
+    shape.sort_unstable();
+
+    let mut hasher = FxHasher::default();
+    for cell in shape.iter() {
+        hasher.write_i32(cell.0);
+        hasher.write_i32(cell.1);
+    }
+
+    (hasher.finish(), shape, (left, bottom))
+}
+
+impl PeriodDetector {
+    // Returns the behavior if the cells have been seen before.
+    pub fn observe(&mut self, generation: usize, cells: &[Cell]) -> Option<Behavior> {
+        if self.last_generation.is_some_and(|last| last + 1 != generation) {
+            self.reset();
+        }
+        self.last_generation = Some(generation);
+
+        if cells.is_empty() {
+            return Some(Behavior::DiesOut { generation });
+        }
+
+        let (hash, shape, position) = shape_hash(cells);
+        let behavior = self.seen.get(&hash).filter(|seen| seen.shape == shape).map(|seen| {
+            Behavior::Periodic {
+                period: generation - seen.generation,
+                displacement: (
+                    position.0.wrapping_sub(seen.position.0),
+                    position.1.wrapping_sub(seen.position.1),
+                ),
+            }
+        });
+
+        self.shape_cells += shape.len();
+        if let Some(replaced) = self.seen.insert(hash, Seen { generation, position, shape }) {
+            self.shape_cells -= replaced.shape.len();
+        }
+        self.order.push_back((hash, generation));
+        while self.order.len() > self.max_period || self.shape_cells > SHAPE_CELL_LIMIT {
+            let Some((oldest, oldest_generation)) = self.order.pop_front() else {
+                break;
+            };
+            // The hash may have been seen again since, which is kept.
+            if self.seen.get(&oldest).is_some_and(|seen| seen.generation == oldest_generation) {
+                self.shape_cells -= self.seen.remove(&oldest).unwrap().shape.len();
+            }
+        }
+
+        behavior
+    }
+
+    pub fn reset(&mut self) {
+        self.seen.clear();
+        self.order.clear();
+        self.shape_cells = 0;
+        self.last_generation = None;
+    }
+}
+
+// Runs the cells until they repeat themselves, or the generations run out.
+pub fn analyze(state: &mut dyn State, max_generations: usize) -> Option<Behavior> {
+    let mut detector = period_detector(max_generations + 1);
+
+    for _ in 0..=max_generations {
+        if let Some(behavior) = detector.observe(state.generation(), &state.collect_cells()) {
+            return Some(behavior);
+        }
+        state.tick();
+    }
+
+    None
+}
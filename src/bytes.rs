@@ -0,0 +1,51 @@
+use crate::state::Cell;
+
+// Cells are encoded as 8 bytes each, x and then y, in little endian.
+
+pub fn from_bytes_to_cells(bytes: Vec<u8>) -> Vec<Cell> {
+    let cell_amount = (bytes.len() - (bytes.len() % 8)) / 8;
+    let mut collection = Vec::default();
+
+    if cell_amount > 0 {
+        for i in 0..cell_amount {
+            let i = i * 8;
+            let cell_x = i32::from_le_bytes([
+                bytes[i], 
+                bytes[i+1], 
+                bytes[i+2], 
+                bytes[i+3],
+            ]);
+
+            let cell_y = i32::from_le_bytes([
+                bytes[i+4], 
+                bytes[i+5], 
+                bytes[i+6], 
+                bytes[i+7],
+            ]);
+
+            collection.push((cell_x, cell_y));
+        }
+    }
+
+    collection
+}
+
+pub fn from_cells_to_bytes(collection: Vec<Cell>) -> Vec<u8> {
+    let mut bytes = Vec::default();
+
+    for cell in collection {
+        let x_bytes = cell.0.to_le_bytes();
+        bytes.push(x_bytes[0]);
+        bytes.push(x_bytes[1]);
+        bytes.push(x_bytes[2]);
+        bytes.push(x_bytes[3]);
+        
+        let y_bytes = cell.1.to_le_bytes();
+        bytes.push(y_bytes[0]);
+        bytes.push(y_bytes[1]);
+        bytes.push(y_bytes[2]);
+        bytes.push(y_bytes[3]);
+    }
+
+    bytes
+}
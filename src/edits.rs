@@ -0,0 +1,172 @@
+use crate::state::{Cell, State};
+
+// Most edits remembered, dropping the oldest ones after.
+const EDIT_LIMIT: usize = 1000;
+
+// Cells given life and cells killed by hand, in one go.
+#[derive(Default)]
+struct Edit {
+    inserted: Vec<Cell>,
+    removed: Vec<Cell>,
+}
+
+impl Edit {
+    fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.removed.is_empty()
+    }
+
+    // Adds the changes of a later edit, so that undoing the whole puts every
+    // cell back the way it was before the first.
+    fn merge(&mut self, later: Edit) {
+        for cell in later.inserted {
+            match self.removed.iter().position(|removed| *removed == cell) {
+                Some(index) => {
+                    self.removed.swap_remove(index);
+                }
+                None => self.inserted.push(cell),
+            }
+        }
+        for cell in later.removed {
+            match self.inserted.iter().position(|inserted| *inserted == cell) {
+                Some(index) => {
+                    self.inserted.swap_remove(index);
+                }
+                None => self.removed.push(cell),
+            }
+        }
+    }
+}
+
+// Changes made to the cells by hand, which can be undone and redone apart from
+// the generations that have passed since.
+pub struct Edits {
+    undone: Vec<Edit>,
+    redone: Vec<Edit>,
+    // Changes of the stroke being made, to be undone together.
+    stroke: Option<Edit>,
+}
+
+pub fn edits() -> Edits {
+    Edits {
+        undone: Vec::new(),
+        redone: Vec::new(),
+        stroke: None,
+    }
+}
+
+impl Edits {
+    // Gives life to the cells, remembering those that were dead. Returns
+    // whether any cell was.
+    pub fn insert_cells(&mut self, state: &mut dyn State, cells: Vec<Cell>) -> bool {
+        let topology = state.topology();
+        let mut inserted: Vec<Cell> = cells
+            .into_iter()
+            .filter_map(|cell| topology.wrap(cell))
+            .filter(|cell| !state.is_alive(*cell))
+            .collect();
+        inserted.sort_unstable();
+        inserted.dedup();
+
+        state.insert_cells(inserted.clone());
+        let changed = !inserted.is_empty();
+        self.record(Edit {
+            inserted,
+            removed: Vec::new(),
+        });
+        changed
+    }
+
+    // Kills the cells, remembering those that were alive. Returns whether any
+    // cell was.
+    pub fn remove_cells(&mut self, state: &mut dyn State, cells: Vec<Cell>) -> bool {
+        let topology = state.topology();
+        let mut removed: Vec<Cell> = cells
+            .into_iter()
+            .filter_map(|cell| topology.wrap(cell))
+            .filter(|cell| state.is_alive(*cell))
+            .collect();
+        removed.sort_unstable();
+        removed.dedup();
+
+        state.remove_cells(removed.clone());
+        let changed = !removed.is_empty();
+        self.record(Edit {
+            inserted: Vec::new(),
+            removed,
+        });
+        changed
+    }
+
+    pub fn toggle_cell(&mut self, state: &mut dyn State, cell: Cell) -> bool {
+        match state.is_alive(cell) {
+            true => self.remove_cells(state, vec![cell]),
+            _ => self.insert_cells(state, vec![cell]),
+        }
+    }
+
+    // Groups the following changes into a single edit, until the stroke ends.
+    pub fn start_stroke(&mut self) {
+        self.end_stroke();
+        self.stroke = Some(Edit::default());
+    }
+
+    pub fn end_stroke(&mut self) {
+        if let Some(stroke) = self.stroke.take() {
+            self.push(stroke);
+        }
+    }
+
+    fn record(&mut self, edit: Edit) {
+        match self.stroke.as_mut() {
+            Some(stroke) => stroke.merge(edit),
+            None => self.push(edit),
+        }
+    }
+
+    // A new edit cannot be redone past.
+    fn push(&mut self, edit: Edit) {
+        if edit.is_empty() {
+            return;
+        }
+
+        self.undone.push(edit);
+        self.redone.clear();
+        if self.undone.len() > EDIT_LIMIT {
+            self.undone.remove(0);
+        }
+    }
+
+    // Reverts the last edit, returning false if there was none.
+    pub fn undo(&mut self, state: &mut dyn State) -> bool {
+        self.end_stroke();
+        let Some(edit) = self.undone.pop() else {
+            return false;
+        };
+
+        state.remove_cells(edit.inserted.clone());
+        state.insert_cells(edit.removed.clone());
+        self.redone.push(edit);
+        true
+    }
+
+    // Makes the last undone edit again, returning false if there was none.
+    pub fn redo(&mut self, state: &mut dyn State) -> bool {
+        self.end_stroke();
+        let Some(edit) = self.redone.pop() else {
+            return false;
+        };
+
+        state.remove_cells(edit.removed.clone());
+        state.insert_cells(edit.inserted.clone());
+        self.undone.push(edit);
+        true
+    }
+
+    pub fn undo_amount(&self) -> usize {
+        self.undone.len()
+    }
+
+    pub fn redo_amount(&self) -> usize {
+        self.redone.len()
+    }
+}
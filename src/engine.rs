@@ -0,0 +1,213 @@
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use crate::active;
+use crate::hashlife;
+use crate::parallel;
+use crate::single;
+use crate::state::State;
+use crate::tiles;
+
+// Environment variable with the amount of threads to tick with, when it is
+// not given on the command line.
+pub const THREADS_VARIABLE: &str = "GOL_THREADS";
+
+// Threads to tick with, as set in the environment variable, or every thread
+// available if it is not set.
+pub fn thread_amount() -> Result<usize, String> {
+    match env::var(THREADS_VARIABLE) {
+        Ok(threads) => threads
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|threads| *threads > 0)
+            .ok_or(format!("{} should be an amount of threads above 0, not '{}'", THREADS_VARIABLE, threads)),
+        Err(_) => Ok(thread::available_parallelism().map_or(1, |threads| threads.get())),
+    }
+}
+
+// The ways of simulating the cells, each implementing State.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Engine {
+    // A hash set of the live cells.
+    Single,
+    // A hash set of the live cells, ticked by several threads.
+    Parallel,
+    // A memoized quadtree, which can leap ahead by many generations at once.
+    HashLife,
+    // 64 by 64 tiles of bits.
+    Tiles,
+    // A hash set of the live cells, only looking where the last tick changed
+    // something.
+    Active,
+}
+
+impl Engine {
+    pub const ALL: [Engine; 5] = [Engine::Single, Engine::Parallel, Engine::HashLife, Engine::Tiles, Engine::Active];
+
+    // The parallel engine if there is more than one thread to tick with, and
+    // the single one otherwise.
+    pub fn auto(thread_amount: usize) -> Engine {
+        match thread_amount {
+            0 | 1 => Engine::Single,
+            _ => Engine::Parallel,
+        }
+    }
+
+    // Builds a state of the engine. The parallel engine ticks with the amount
+    // of threads, and falls back to the single one with only one thread.
+    pub fn build(self, thread_amount: usize) -> Box<dyn State> {
+        match self {
+            Engine::Single => Box::new(single::single_state()),
+            Engine::Parallel if thread_amount <= 1 => Box::new(single::single_state()),
+            Engine::Parallel => Box::new(parallel::parallel_state_with_threads(thread_amount)),
+            Engine::HashLife => Box::new(hashlife::hashlife_state()),
+            Engine::Tiles => Box::new(tiles::tile_state()),
+            Engine::Active => Box::new(active::active_state()),
+        }
+    }
+
+    // Whether the engine knows the changes of its ticks, which heat maps are
+    // made from.
+    pub fn counts_changes(self) -> bool {
+        self != Engine::HashLife
+    }
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Engine::ALL
+            .into_iter()
+            .find(|engine| engine.to_string().eq_ignore_ascii_case(name.trim()))
+            .ok_or(format!("engine '{}' should be single, parallel, hashlife, tiles or active", name))
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Engine::Single => "single",
+            Engine::Parallel => "parallel",
+            Engine::HashLife => "hashlife",
+            Engine::Tiles => "tiles",
+            Engine::Active => "active",
+        };
+        f.pad(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::Rule;
+    use crate::soup::Soup;
+    use crate::state::Cell;
+    use crate::topology::Topology;
+
+    const GENERATIONS: usize = 300;
+
+    fn soup(width: u32, height: u32, seed: u64, center: Cell) -> Vec<Cell> {
+        Soup { width, height, density: 0.4, seed }.cells(center)
+    }
+
+    fn sorted_cells(state: &dyn State) -> Vec<Cell> {
+        let mut cells = state.collect_cells();
+        cells.sort_unstable();
+        cells
+    }
+
+    fn start(engine: Engine, cells: &[Cell], rule: Rule, topology: Topology) -> Box<dyn State> {
+        let mut state = engine.build(2);
+        state.set_rule(rule);
+        state.set_topology(topology).unwrap();
+        state.insert_cells(cells.to_vec());
+        state
+    }
+
+    // Ticks the cells on every engine that supports the topology, checking
+    // that they all end up with the same cells as the single one.
+    fn assert_engines_agree(cells: &[Cell], rule: Rule, topology: Topology) {
+        let mut expected = start(Engine::Single, cells, rule, topology);
+        for _ in 0..GENERATIONS {
+            expected.tick();
+        }
+
+        for engine in Engine::ALL {
+            let mut state = engine.build(2);
+            state.set_rule(rule);
+            if state.set_topology(topology).is_err() {
+                continue;
+            }
+            state.insert_cells(cells.to_vec());
+            for _ in 0..GENERATIONS {
+                state.tick();
+            }
+            assert_eq!(state.generation(), expected.generation(), "{} on {}", engine, topology);
+            assert_eq!(sorted_cells(&*state), sorted_cells(&*expected), "{} on {}", engine, topology);
+        }
+    }
+
+    #[test]
+    fn engines_agree_on_soups() {
+        for (seed, rule) in [(1, "B3/S23"), (2, "B36/S23"), (3, "B3678/S34678")] {
+            let cells = soup(48, 48, seed, (0, 0));
+            assert_engines_agree(&cells, rule.parse().unwrap(), Topology::Unbounded);
+        }
+    }
+
+    #[test]
+    fn engines_agree_on_bounded_topologies() {
+        let (width, height) = (48, 40);
+        let cells = soup(width, height, 5, (0, 0));
+        for topology in [
+            Topology::Plane { width, height },
+            Topology::Torus { width, height },
+            Topology::KleinBottle { width, height },
+            Topology::CrossSurface { width, height },
+        ] {
+            assert_engines_agree(&cells, Rule::default(), topology);
+        }
+    }
+
+    #[test]
+    fn engines_agree_across_the_edge_of_the_universe() {
+        for (seed, center) in [(6, (i32::MAX, 0)), (7, (0, i32::MIN)), (8, (i32::MAX, i32::MIN))] {
+            let cells = soup(32, 32, seed, center);
+            assert_engines_agree(&cells, Rule::default(), Topology::Unbounded);
+        }
+    }
+
+    #[test]
+    fn hashlife_leaps_agree_with_ticks() {
+        for center in [(0, 0), (i32::MAX, i32::MIN)] {
+            let cells = soup(32, 32, 9, center);
+            let mut ticked = start(Engine::Single, &cells, Rule::default(), Topology::Unbounded);
+            let mut leaped = start(Engine::HashLife, &cells, Rule::default(), Topology::Unbounded);
+            for _ in 0..256 {
+                ticked.tick();
+            }
+            leaped.step(8);
+
+            assert_eq!(leaped.generation(), ticked.generation());
+            assert_eq!(sorted_cells(&*leaped), sorted_cells(&*ticked));
+        }
+    }
+
+    #[test]
+    fn unsupported_topologies_are_refused() {
+        let topology = Topology::Torus { width: 10, height: 10 };
+        for engine in Engine::ALL {
+            let mut state = engine.build(2);
+            state.insert_cells(vec![(20, 20)]);
+            let result = state.set_topology(topology);
+            let refused = matches!(engine, Engine::HashLife | Engine::Tiles);
+            assert_eq!(result.is_err(), refused, "{}", engine);
+            if result.is_err() {
+                assert_eq!(state.collect_cells(), vec![(20, 20)], "{}", engine);
+            }
+        }
+    }
+}
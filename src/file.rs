@@ -1,35 +1,102 @@
 use std::fs;
 use crate::state::Cell;
+use crate::rle::{self, Pattern};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
 
 // Character order from: https://stackoverflow.com/a/74186686
 const ORDER: &str = " `.-':_,^=;><+!rc*/z?sLTv)J7(|Fi{C}fI31tlu[neoZ5Yxjya]2ESwqkP6h9d4VpOGbUAKXHm8RD#$Bg0MNWQ%&@";
 
 pub fn cells_from_file(file_path: String) -> Vec<Cell> {
+    pattern_from_file(&file_path)
+        .map(|pattern| pattern.cells)
+        .unwrap_or_default()
+}
+
+// Reads RLE files and images, or guesses the cells from a picture made of
+// characters.
+pub fn pattern_from_file(file_path: &str) -> Result<Pattern, String> {
+    let bytes = fs::read(file_path)
+        .map_err(|error| format!("could not read '{}': {}", file_path, error))?;
+
+    // Images are recognized by their first bytes, whatever they are named.
+    if bytes.starts_with(PNG_SIGNATURE) || is_bmp(&bytes) {
+        return Ok(Pattern {
+            cells: cells_from_image(&bytes).map_err(|error| format!("could not read '{}': {}", file_path, error))?,
+            ..Default::default()
+        });
+    }
+
+    let contents = String::from_utf8(bytes)
+        .map_err(|error| format!("could not read '{}': {}", file_path, error))?;
+
+    let first_line = contents
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty() && !line.starts_with('#'));
+
+    if file_path.to_lowercase().ends_with(".rle") || first_line.is_some_and(|line| line.starts_with("x ") || line.starts_with("x=")) {
+        return rle::parse(&contents).map_err(|error| format!("could not parse '{}': {}", file_path, error));
+    }
+
+    Ok(Pattern {
+        cells: cells_from_text(&contents),
+        ..Default::default()
+    })
+}
+
+// BMP files start with "BM", which text could too, so the size of the header
+// following the file header is checked as well.
+fn is_bmp(bytes: &[u8]) -> bool {
+    bytes.len() >= 18
+        && bytes.starts_with(b"BM")
+        && [12, 40, 52, 56, 108, 124].contains(&u32::from_le_bytes([bytes[14], bytes[15], bytes[16], bytes[17]]))
+}
+
+// Turns the pixels of a black and white image into cells, with the top row at
+// y = 0, like in RLE files. Dark pixels are cells, unless most of the image is
+// dark, in which case the light pixels are.
+fn cells_from_image(bytes: &[u8]) -> Result<Vec<Cell>, String> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|error| error.to_string())?
+        .to_luma_alpha8();
+
+    // Transparent pixels are always empty space.
+    let dark = |pixel: &image::LumaA<u8>| pixel[1] >= 128 && pixel[0] < 128;
+    let dark_amount = image.pixels().filter(|pixel| dark(pixel)).count();
+    let cells_are_dark = dark_amount * 2 <= (image.width() * image.height()) as usize;
+
+    Ok(image
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel[1] >= 128 && dark(pixel) == cells_are_dark)
+        .map(|(x, y, _)| (x as i32, -(y as i32)))
+        .collect())
+}
+
+fn cells_from_text(contents: &str) -> Vec<Cell> {
     let mut cells = Vec::default();
 
     let mut cell_char: Option<char> = None;
     let mut _space_char: Option<char> = None;
 
-    let contents = fs::read_to_string(file_path).unwrap_or(String::default());
-    
     // Figure out which character fills most space.
     for character in contents.chars() {
-        if cell_char != None && _space_char != None {
+        if cell_char.is_some() && _space_char.is_some() {
             if ORDER.find(cell_char.unwrap()) < ORDER.find(_space_char.unwrap()) {
                 (cell_char, _space_char) = (_space_char, cell_char);
             }
             break;
         }
-        else if cell_char != None && character != cell_char.unwrap() {
+        else if cell_char.is_some() && character != cell_char.unwrap() {
             _space_char = Some(character);
         }
-        else if cell_char == None {
+        else if cell_char.is_none() {
             cell_char = Some(character);
         }
     }
 
     // Return empty cells collection if all characters are the same
-    if cell_char == None || _space_char == None {
+    if cell_char.is_none() || _space_char.is_none() {
         return cells;
     }
 
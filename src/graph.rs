@@ -0,0 +1,123 @@
+use nannou::color::Rgb;
+use nannou::prelude::{Draw, Rect};
+use std::collections::VecDeque;
+use crate::state::State;
+
+const WIDTH: f32 = 300.0;
+const HEIGHT: f32 = 120.0;
+const MARGIN: f32 = 10.0;
+
+// Population, births and deaths at one generation.
+struct Sample {
+    generation: usize,
+    population: usize,
+    // Unknown if the engine does not know the changes of its ticks, or more
+    // than one generation was advanced.
+    changes: Option<(usize, usize)>,
+}
+
+// Population, births and deaths over the last generations, drawn as a graph.
+pub struct PopulationGraph {
+    samples: VecDeque<Sample>,
+    length: usize,
+}
+
+pub fn population_graph(length: usize) -> PopulationGraph {
+    PopulationGraph {
+        samples: VecDeque::with_capacity(length),
+        length,
+    }
+}
+
+impl PopulationGraph {
+    pub fn observe(&mut self, state: &dyn State, with_changes: bool) {
+        // Going back in time forgets what came after.
+        let generation = state.generation();
+        while self.samples.back().is_some_and(|sample| sample.generation >= generation) {
+            self.samples.pop_back();
+        }
+
+        let changes = match with_changes {
+            true => state.last_changes().map(|changes| (changes.births.len(), changes.deaths.len())),
+            _ => None,
+        };
+        self.samples.push_back(Sample {
+            generation,
+            population: state.count_cells(),
+            changes,
+        });
+
+        while self.samples.len() > self.length {
+            self.samples.pop_front();
+        }
+    }
+
+    // Draws the graph in the lower right corner of the window. Population is
+    // scaled to the height of the graph, and births and deaths are scaled
+    // together, so they can be told apart from each other.
+    pub fn draw(&self, draw: &Draw, window: Rect, color: Rgb) {
+        let (left, bottom) = (window.right() - MARGIN - WIDTH, window.bottom() + MARGIN);
+        draw.rect()
+            .x_y(left + WIDTH / 2.0, bottom + HEIGHT / 2.0)
+            .w_h(WIDTH, HEIGHT)
+            .no_fill()
+            .stroke(color)
+            .stroke_weight(1.0);
+
+        if self.samples.len() < 2 {
+            return;
+        }
+
+        let max_population = self.samples.iter().map(|sample| sample.population).max().unwrap_or(0).max(1);
+        let max_changes = self.samples
+            .iter()
+            .filter_map(|sample| sample.changes)
+            .map(|(births, deaths)| births.max(deaths))
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let x = |index: usize| left + index as f32 / (self.length.max(2) - 1) as f32 * WIDTH;
+        let y = |value: usize, max: usize| bottom + value as f32 / max as f32 * HEIGHT;
+
+        let birth_color = Rgb::from_components((0.2, 0.8, 0.2));
+        let death_color = Rgb::from_components((0.9, 0.2, 0.2));
+
+        // Births and deaths are drawn in pieces, broken where they are unknown.
+        for (series, series_color) in [(0, birth_color), (1, death_color)] {
+            let mut pieces = vec![Vec::new()];
+            for (index, sample) in self.samples.iter().enumerate() {
+                match sample.changes {
+                    Some(changes) => {
+                        let value = if series == 0 { changes.0 } else { changes.1 };
+                        pieces.last_mut().unwrap().push(((x(index), y(value, max_changes)), series_color));
+                    }
+                    None => pieces.push(Vec::new()),
+                }
+            }
+
+            for piece in pieces.into_iter().filter(|piece| piece.len() > 1) {
+                draw.polyline().weight(1.0).points_colored(piece);
+            }
+        }
+
+        let population_points = self.samples
+            .iter()
+            .enumerate()
+            .map(|(index, sample)| ((x(index), y(sample.population, max_population)), color));
+        draw.polyline().weight(1.5).points_colored(population_points);
+
+        let labels = [
+            (format!("Population, up to {}", max_population), color),
+            (format!("Births, up to {}", max_changes), birth_color),
+            (format!("Deaths, up to {}", max_changes), death_color),
+        ];
+        for (index, (label, label_color)) in labels.iter().enumerate() {
+            draw.text(label)
+                .x(left + 100.0)
+                .y(bottom + HEIGHT + 27.5 - index as f32 * 10.0)
+                .color(*label_color)
+                .left_justify();
+        }
+    }
+}
@@ -4,6 +4,7 @@ use nannou::prelude::Rect;
 use nannou::prelude::{App, Frame, MouseButton::Left, MouseButton::Right, Update, Vec2};
 use nannou::window;
 use nannou::winit::event::ElementState::{Pressed, Released};
+use nannou::winit::event::ModifiersState;
 use nannou::winit::event::WindowEvent;
 use nannou::winit::event::WindowEvent::{
     KeyboardInput,
@@ -12,30 +13,156 @@ use nannou::winit::event::WindowEvent::{
     MouseWheel,
     HoveredFile,
     DroppedFile,
-    HoveredFileCancelled     
+    HoveredFileCancelled,
+    ModifiersChanged,
 };
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 use nannou::color::Rgb;
 use std::sync::Mutex;
+use std::fs;
 use std::io::{self, Write};
+use crate::activity;
+use crate::ages::TRAIL_LENGTH;
+use crate::analysis::{self, Behavior, PeriodDetector};
+use crate::bytes::from_cells_to_bytes;
+use crate::edits::{self, Edits};
+use crate::engine::Engine;
 use crate::file;
-use crate::state::Cell;
+use crate::graph::{self, PopulationGraph};
+use crate::history::{self, History};
+use crate::recording::{self, Recorder};
+use crate::selection;
+use crate::session::{self, Session, BOOKMARK_AMOUNT};
+use crate::soup::Soup;
+use crate::speed::Speed;
+use crate::rle::{self, Pattern};
+use crate::state::{cell_tris, relative_to_view, Cell};
+use crate::telemetry::StatsWriter;
+use crate::rule::Rule;
+use crate::topology::Topology;
+
+// Most live cells to look for repetitions among while the stats are shown.
+const PERIOD_DETECTION_LIMIT: usize = 100000;
+
+// Longest period looked for while the stats are shown.
+const MAX_PERIOD: usize = 1000;
+
+// Longest time spent catching up on ticks in a single update, so the window
+// keeps responding at high speeds.
+const UPDATE_BUDGET: Duration = Duration::from_millis(15);
+
+// What the GUI is started with, as given on the command line.
+pub struct Settings {
+    pub input_cells: Vec<Cell>,
+    pub send_cells_to_stdout: bool,
+    pub rule: Rule,
+    pub topology: Topology,
+    pub engine: Engine,
+    pub thread_amount: usize,
+    pub save_path: Option<String>,
+    pub history_budget: usize,
+    pub rewind_amount: usize,
+    pub soup: Soup,
+    pub speed: Speed,
+    pub record_path: Option<String>,
+    pub record_style: recording::Style,
+    pub stats_writer: Option<StatsWriter>,
+    pub graph_length: usize,
+    pub session_path: Option<String>,
+    // View, zoom, dark mode, bookmarks and generation to start with.
+    pub session: Session,
+}
 
 lazy_static! {
-    static ref INPUT_CELLS: Mutex<Vec<Cell>> = Mutex::new(Vec::new());
-    static ref SEND_CELLS_TO_STDOUT: AtomicBool = AtomicBool::new(false);
+    static ref SETTINGS: Mutex<Option<Settings>> = Mutex::new(None);
 }
 
-pub fn run_gui(mut input_cells: Vec<Cell>, send_cells_to_stdout: bool) {
-    INPUT_CELLS.lock().unwrap().append(&mut input_cells);
-    SEND_CELLS_TO_STDOUT.store(send_cells_to_stdout, Ordering::Relaxed);
+pub fn run_gui(settings: Settings) {
+    *SETTINGS.lock().unwrap() = Some(settings);
     nannou::app(model).update(update).run();
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum Brush {
+    Draw,
+    Erase,
+    Toggle,
+}
+
+impl Brush {
+    fn next(self) -> Brush {
+        match self {
+            Brush::Draw => Brush::Erase,
+            Brush::Erase => Brush::Toggle,
+            Brush::Toggle => Brush::Draw,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Brush::Draw => "Draw",
+            Brush::Erase => "Erase",
+            Brush::Toggle => "Toggle",
+        }
+    }
+}
+
+// How live cells are colored.
+#[derive(Clone, Copy, PartialEq)]
+enum Coloring {
+    Plain,
+    // Young cells are yellow, turning red and then blue as they age.
+    Age,
+    // Recently dead cells fade away behind the live ones.
+    Trail,
+}
+
+impl Coloring {
+    fn next(self) -> Coloring {
+        match self {
+            Coloring::Plain => Coloring::Age,
+            Coloring::Age => Coloring::Trail,
+            Coloring::Trail => Coloring::Plain,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Coloring::Plain => "Plain",
+            Coloring::Age => "By age",
+            Coloring::Trail => "With trails",
+        }
+    }
+}
+
+// Colors of cells by age, from newborn to this old and older.
+const AGE_COLORS: [(f32, f32, f32); 4] = [(1.0, 0.95, 0.4), (1.0, 0.35, 0.1), (0.6, 0.1, 0.6), (0.15, 0.3, 0.9)];
+const OLD_AGE: usize = 1000;
+
+fn age_color(age: usize) -> Rgb {
+    // Ages are spread out logarithmically, so the first few generations stand
+    // out the most.
+    let position = ((1 + age) as f32).ln() / ((1 + OLD_AGE) as f32).ln();
+    let position = position.min(1.0) * (AGE_COLORS.len() - 1) as f32;
+    let index = (position as usize).min(AGE_COLORS.len() - 2);
+    let fraction = position - index as f32;
+
+    let (from, to) = (AGE_COLORS[index], AGE_COLORS[index + 1]);
+    Rgb::from_components((
+        from.0 + (to.0 - from.0) * fraction,
+        from.1 + (to.1 - from.1) * fraction,
+        from.2 + (to.2 - from.2) * fraction,
+    ))
+}
+
 struct Model {
     _window: window::Id,
+    settings: Settings,
     state: Box<dyn crate::state::State>,
+    history: History,
+    edits: Edits,
+    period_detector: PeriodDetector,
+    behavior: Option<Behavior>,
     view: (f64, f64),
     last_view: (f64, f64),
     cursor_location: Vec2,
@@ -43,10 +170,25 @@ struct Model {
     scale: f64,
     clicked: bool,
     show_stats: bool,
+    show_graph: bool,
+    show_heat_map: bool,
+    population_graph: PopulationGraph,
     dark_mode: bool,
     paused: bool,
     drawing: bool,
+    brush: Brush,
+    coloring: Coloring,
+    last_brushed: Option<Cell>,
+    selecting: bool,
+    selection: Option<(Cell, Cell)>,
+    clipboard: Vec<Cell>,
+    pasting: bool,
     hovering_file: bool,
+    modifiers: ModifiersState,
+    bookmarks: [Option<(f64, f64)>; BOOKMARK_AMOUNT],
+    session_path: Option<String>,
+    speed: Speed,
+    recorder: Option<Recorder>,
     last_update: Instant,
 }
 
@@ -61,35 +203,65 @@ fn model(app: &App) -> Model {
     app.main_window().set_title("gol");
     app.set_exit_on_escape(false);
 
-    let view: (f64, f64) = (0.0, 0.0).into();
-    let last_view: (f64, f64) = view.clone();
+    let mut settings = SETTINGS.lock().unwrap().take().unwrap();
+
+    let view: (f64, f64) = settings.session.view;
+    let last_view: (f64, f64) = view;
     let cursor_location: Vec2 = (0.0, 0.0).into();
     let cursor_cell: Cell = (0, 0);
-    let scale: f64 = 10.0;
+    let scale: f64 = settings.session.scale;
     let clicked: bool = false;
     let show_stats: bool = false;
-    let dark_mode: bool = true;
+    let show_graph: bool = false;
+    let show_heat_map: bool = false;
+    let dark_mode: bool = settings.session.dark_mode;
     let paused: bool = true;
     let drawing: bool = false;
+    let brush: Brush = Brush::Draw;
+    let coloring: Coloring = Coloring::Plain;
+    let last_brushed: Option<Cell> = None;
+    let selecting: bool = false;
+    let selection: Option<(Cell, Cell)> = None;
+    let clipboard: Vec<Cell> = Vec::new();
+    let pasting: bool = false;
     let hovering_file: bool = false;
+    let modifiers: ModifiersState = ModifiersState::empty();
+    let bookmarks = settings.session.bookmarks;
+    let session_path = settings.session_path.clone();
 
-    let mut state = crate::state::state();
+    let mut state = settings.engine.build(settings.thread_amount);
 
-    // Spawn random amount of cells in random position within range.
-    /*
-    let cell_amount = random_range(250000, 500000);
-    let mut collection = Vec::default();
-    for _ in 0..cell_amount {
-        let cell = (random_range(-1000, 1000), random_range(-1000, 1000));
-        collection.push(cell);
+    state.set_rule(settings.rule);
+    if let Err(error) = state.set_topology(settings.topology) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+    state.insert_cells(std::mem::take(&mut settings.input_cells));
+    state.set_generation(settings.session.generation);
+    if let Some(stats_writer) = settings.stats_writer.as_mut()
+        && let Err(error) = stats_writer.write(&*state, false)
+    {
+        eprintln!("{}", error);
+        settings.stats_writer = None;
     }
-    state.insert_cells(collection);
-    */
-    state.insert_cells(INPUT_CELLS.lock().unwrap().to_vec());
+
+    let history = history::history(settings.history_budget);
+    let edits = edits::edits();
+    let mut population_graph = graph::population_graph(settings.graph_length);
+    population_graph.observe(&*state, false);
+    let period_detector = analysis::period_detector(MAX_PERIOD);
+    let behavior: Option<Behavior> = None;
+    let mut speed = settings.speed;
+    speed.step_exponent = speed.step_exponent.min(state.max_step_exponent());
 
     Model {
         _window,
+        settings,
         state,
+        history,
+        edits,
+        period_detector,
+        behavior,
         view,
         last_view,
         cursor_location,
@@ -97,23 +269,193 @@ fn model(app: &App) -> Model {
         scale,
         clicked,
         show_stats,
+        show_graph,
+        show_heat_map,
+        population_graph,
         dark_mode,
         paused,
         drawing,
+        brush,
+        coloring,
+        last_brushed,
+        selecting,
+        selection,
+        clipboard,
+        pasting,
         hovering_file,
+        modifiers,
+        bookmarks,
+        session_path,
+        speed,
+        recorder: None,
         last_update: Instant::now(),
     }
 }
     
-fn update_cursor_cell(model: &mut Model) -> () {
+fn update_cursor_cell(model: &mut Model) {
     let (x, y) = (model.cursor_location.x as f64, model.cursor_location.y as f64);
-    let (x, y) = (x / model.scale  as f64, -y / model.scale as f64);
+    let (x, y) = (x / model.scale, -y / model.scale);
     let (x, y) = (x - 0.5, y - 0.5);
     let (x, y) = (x, y + 1.0);
     let (x, y) = (x - model.view.0, y - model.view.1);
     model.cursor_cell = (x.floor() as i32 + 1, y.floor() as i32);
 }
 
+// Advances the cells by one tick of the current step size, and looks for
+// repetitions if the stats are shown. Repetitions can only be spotted one
+// generation at a time.
+fn advance(model: &mut Model) {
+    model.history.advance(&mut *model.state, model.speed.step_exponent);
+    if let Some(recorder) = model.recorder.as_mut() {
+        recorder.capture(&*model.state);
+    }
+    // Births and deaths are only those of the last tick, so they are left out
+    // when advancing several generations at once.
+    let with_changes = model.speed.step_exponent == 0;
+    model.population_graph.observe(&*model.state, with_changes);
+    if let Some(stats_writer) = model.settings.stats_writer.as_mut()
+        && let Err(error) = stats_writer.write(&*model.state, with_changes)
+    {
+        eprintln!("{}", error);
+        model.settings.stats_writer = None;
+    }
+
+    if model.show_stats
+        && model.speed.step_exponent == 0
+        && model.state.count_cells() <= PERIOD_DETECTION_LIMIT
+    {
+        model.behavior = model.period_detector.observe(
+            model.state.generation(),
+            &model.state.collect_cells()
+        );
+    } else {
+        model.behavior = None;
+    }
+}
+
+// Files written from the GUI are named after the time they were started,
+// unless a path was given.
+fn timestamped_path(extension: &str) -> String {
+    let milliseconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0);
+    format!("gol-{}.{}", milliseconds, extension)
+}
+
+// Starts recording the selection, or the cells in view if nothing is selected.
+// Stops and writes the recording if one is going on.
+fn toggle_recording(app: &App, model: &mut Model) {
+    if let Some(recorder) = model.recorder.take() {
+        if let Err(error) = recorder.finish() {
+            eprintln!("{}", error);
+        }
+        return;
+    }
+
+    let (min, max) = match model.selection {
+        Some((corner, other_corner)) => selection::bounds(corner, other_corner),
+        None => {
+            let (half_width, half_height) = (
+                app.window_rect().w() as f64 / model.scale / 2.0,
+                app.window_rect().h() as f64 / model.scale / 2.0,
+            );
+            (
+                ((-model.view.0 - half_width).ceil() as i32, (-model.view.1 - half_height).ceil() as i32),
+                ((-model.view.0 + half_width).floor() as i32, (-model.view.1 + half_height).floor() as i32),
+            )
+        }
+    };
+
+    let path = model.settings.record_path.clone().unwrap_or_else(|| timestamped_path("gif"));
+
+    let mut recorder = match recording::recorder(path, min, max, model.settings.record_style) {
+        Ok(recorder) => recorder,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+    recorder.capture(&*model.state);
+    model.recorder = Some(recorder);
+}
+
+// Saves the session to the path it was started with, or to a new file if
+// there is none yet, which later saves and loads then use.
+fn save_session(model: &mut Model) {
+    let mut session = Session {
+        view: model.view,
+        scale: model.scale,
+        dark_mode: model.dark_mode,
+        bookmarks: model.bookmarks,
+        ..Default::default()
+    };
+    session.capture(&*model.state);
+
+    let path = model.session_path.get_or_insert_with(|| timestamped_path("session"));
+    if let Err(error) = session::write(path, &session) {
+        eprintln!("{}", error);
+    }
+}
+
+// Goes back to the last saved session. What came before it is forgotten, so
+// it can neither be stepped back to nor undone.
+fn load_session(model: &mut Model) {
+    let Some(path) = model.session_path.as_ref() else {
+        eprintln!("there is no session to load, save one first or start with --session");
+        return;
+    };
+    let session = match session::read(path) {
+        Ok(session) => session,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+    if let Err(error) = session.restore(&mut *model.state) {
+        eprintln!("could not load session '{}': {}", path, error);
+        return;
+    }
+    model.history.clear();
+    model.edits = edits::edits();
+    model.period_detector.reset();
+    model.behavior = None;
+    model.population_graph = graph::population_graph(model.settings.graph_length);
+    model.population_graph.observe(&*model.state, false);
+
+    model.last_view = model.view;
+    model.view = session.view;
+    model.scale = session.scale;
+    model.dark_mode = session.dark_mode;
+    model.bookmarks = session.bookmarks;
+    update_cursor_cell(model);
+}
+
+// Cells changed by hand or by going back are not a generation following the
+// last one, so repetitions are looked for anew.
+fn edited(model: &mut Model, changed: bool) {
+    if changed {
+        model.period_detector.reset();
+        model.behavior = None;
+    }
+}
+
+// Applies the brush to the cell under the cursor. Toggling is only done once
+// per cell in a stroke, so the cell does not flicker.
+fn paint(model: &mut Model) {
+    if model.brush == Brush::Toggle && model.last_brushed == Some(model.cursor_cell) {
+        return;
+    }
+
+    let changed = match model.brush {
+        Brush::Draw => model.edits.insert_cells(&mut *model.state, vec![model.cursor_cell]),
+        Brush::Erase => model.edits.remove_cells(&mut *model.state, vec![model.cursor_cell]),
+        Brush::Toggle => model.edits.toggle_cell(&mut *model.state, model.cursor_cell),
+    };
+    edited(model, changed);
+    model.last_brushed = Some(model.cursor_cell);
+}
+
 // https://docs.rs/winit/0.28.7/winit/event/enum.WindowEvent.html
 fn raw_window_event(app: &App, model: &mut Model, winit_event: &WindowEvent) {
     match winit_event {
@@ -135,37 +477,149 @@ fn raw_window_event(app: &App, model: &mut Model, winit_event: &WindowEvent) {
                         update_cursor_cell(model);
                     }
                     Some(H) => {
-                        model.last_view = model.view.clone();
-                        model.view = (0.0, 0.0).into();
+                        model.last_view = model.view;
+                        model.view = (0.0, 0.0);
                         update_cursor_cell(model);
                     }
                     Some(A) => model.view = ((i32::MAX - 1) as f64, (i32::MAX - 1) as f64),
-                    Some(J) => {
-                        if model.state.count_cells() != 0 {
-                            model.last_view = model.view.clone();
-                            let random_cell = model.state.random_cell();
-                            model.view = (-random_cell.0 as f64, -random_cell.1 as f64);
-                            update_cursor_cell(model);
+                    Some(J) if model.state.count_cells() != 0 => {
+                        model.last_view = model.view;
+                        let random_cell = model.state.random_cell();
+                        model.view = (-random_cell.0 as f64, -random_cell.1 as f64);
+                        update_cursor_cell(model);
+                    }
+                    Some(key @ (Key1 | Key2 | Key3 | Key4 | Key5 | Key6 | Key7 | Key8 | Key9)) => {
+                        let index = key as usize - Key1 as usize;
+                        match model.modifiers.ctrl() {
+                            true => model.bookmarks[index] = Some(model.view),
+                            _ => if let Some(bookmark) = model.bookmarks[index] {
+                                model.last_view = model.view;
+                                model.view = bookmark;
+                                update_cursor_cell(model);
+                            }
                         }
                     }
+                    Some(S) if model.modifiers.ctrl() => save_session(model),
+                    Some(O) if model.modifiers.ctrl() => load_session(model),
+                    Some(Z) if model.modifiers.ctrl() && model.modifiers.shift() => {
+                        let changed = model.edits.redo(&mut *model.state);
+                        edited(model, changed);
+                    }
+                    Some(Z) if model.modifiers.ctrl() => {
+                        let changed = model.edits.undo(&mut *model.state);
+                        edited(model, changed);
+                    }
+                    Some(Y) if model.modifiers.ctrl() => {
+                        let changed = model.edits.redo(&mut *model.state);
+                        edited(model, changed);
+                    }
                     Some(Z) => {
-                        let current_view = model.view.clone();
-                        model.view = model.last_view;
-                        model.last_view = current_view;
+                        std::mem::swap(&mut model.view, &mut model.last_view);
                         update_cursor_cell(model);
                     }
                     Some(Space) => model.paused = !model.paused,
-                    Some(T) => {
-                        model.state.tick();
+                    Some(T) => advance(model),
+                    Some(Back) => {
+                        model.paused = true;
+                        let changed = model.history.step_back(&mut *model.state);
+                        edited(model, changed);
+                    }
+                    Some(B) => {
+                        model.paused = true;
+                        let generation = model.state.generation();
+                        model.history.rewind(&mut *model.state, model.settings.rewind_amount);
+                        let changed = model.state.generation() != generation;
+                        edited(model, changed);
+                    }
+                    Some(RBracket) => model.speed.faster(),
+                    Some(LBracket) => model.speed.slower(),
+                    Some(Period) => model.speed.bigger_steps(model.state.max_step_exponent()),
+                    Some(Comma) => model.speed.smaller_steps(),
+                    Some(G) => toggle_recording(app, model),
+                    Some(I) => app.main_window().capture_frame(timestamped_path("png")),
+                    Some(E) => model.brush = model.brush.next(),
+                    Some(W) => {
+                        // The heat map starts out cold each time it is shown.
+                        model.show_heat_map = !model.show_heat_map;
+                        model.state.set_activity_tracking(model.show_heat_map);
+                    }
+                    Some(M) => {
+                        model.coloring = model.coloring.next();
+                        model.state.set_age_tracking(model.coloring != Coloring::Plain);
+                    }
+                    Some(N) => {
+                        // Each soup gets its own seed, following the one given.
+                        let changed = model.edits.insert_cells(&mut *model.state, model.settings.soup.cells(model.cursor_cell));
+                        edited(model, changed);
+                        model.settings.soup.seed = model.settings.soup.seed.wrapping_add(1);
+                    }
+                    Some(S) => {
+                        model.selecting = !model.selecting;
+                        model.selection = None;
+                        model.drawing = false;
+                        model.pasting = false;
+                    }
+                    Some(Y) => {
+                        if let Some((corner, other_corner)) = model.selection {
+                            let (min, max) = selection::bounds(corner, other_corner);
+                            model.clipboard = selection::normalize(model.state.cells_in_rect(min, max));
+                        }
                     }
+                    Some(X) => {
+                        if let Some((corner, other_corner)) = model.selection {
+                            let (min, max) = selection::bounds(corner, other_corner);
+                            let cells = model.state.cells_in_rect(min, max);
+                            model.clipboard = selection::normalize(cells.clone());
+                            let changed = model.edits.remove_cells(&mut *model.state, cells);
+                            edited(model, changed);
+                        }
+                    }
+                    Some(Delete) => {
+                        if let Some((corner, other_corner)) = model.selection {
+                            let (min, max) = selection::bounds(corner, other_corner);
+                            let cells = model.state.cells_in_rect(min, max);
+                            let changed = model.edits.remove_cells(&mut *model.state, cells);
+                            edited(model, changed);
+                        }
+                    }
+                    Some(P) if !model.clipboard.is_empty() => {
+                        model.pasting = !model.pasting;
+                        model.selecting = false;
+                        model.selection = None;
+                        model.drawing = false;
+                    }
+                    Some(R) => model.clipboard = selection::rotate(std::mem::take(&mut model.clipboard)),
+                    Some(F) => model.clipboard = selection::flip_horizontally(std::mem::take(&mut model.clipboard)),
+                    Some(V) => model.clipboard = selection::flip_vertically(std::mem::take(&mut model.clipboard)),
                     Some(Escape) => {
-                        if SEND_CELLS_TO_STDOUT.load(Ordering::Relaxed) {
+                        if model.settings.send_cells_to_stdout {
                             let _ = io::stdout().lock().write_all(
                                 &from_cells_to_bytes(
                                     model.state.collect_cells()
                                 )
                             );
                         }
+                        if model.recorder.is_some() {
+                            toggle_recording(app, model);
+                        }
+                        if let Some(stats_writer) = model.settings.stats_writer.as_mut()
+                            && let Err(error) = stats_writer.flush()
+                        {
+                            eprintln!("{}", error);
+                        }
+                        if model.session_path.is_some() {
+                            save_session(model);
+                        }
+                        if let Some(file_path) = model.settings.save_path.as_ref() {
+                            let pattern = Pattern {
+                                cells: model.state.collect_cells(),
+                                rule: Some(model.state.rule()),
+                                ..Default::default()
+                            };
+                            if let Err(error) = fs::write(file_path, rle::write(&pattern)) {
+                                eprintln!("could not save '{}': {}", file_path, error);
+                            }
+                        }
                         app.quit();
                     }
                     _ => (),
@@ -174,6 +628,7 @@ fn raw_window_event(app: &App, model: &mut Model, winit_event: &WindowEvent) {
                 match input.virtual_keycode {
                     Some(Tab) => model.show_stats = !model.show_stats,
                     Some(C) => model.dark_mode = !model.dark_mode,
+                    Some(L) => model.show_graph = !model.show_graph,
                     _ => (),
                 }
             }
@@ -188,7 +643,10 @@ fn raw_window_event(app: &App, model: &mut Model, winit_event: &WindowEvent) {
             update_cursor_cell(model);
 
             if model.drawing && model.clicked {
-                model.state.insert_cell(model.cursor_cell);
+                paint(model);
+            }
+            if model.clicked && let Some((_, other_corner)) = model.selection.as_mut() {
+                *other_corner = model.cursor_cell;
             }
         }
         MouseInput {
@@ -198,20 +656,36 @@ fn raw_window_event(app: &App, model: &mut Model, winit_event: &WindowEvent) {
         } => {
             model.clicked = true;
             if model.drawing && model.clicked {
-                model.state.insert_cell(model.cursor_cell);
+                model.last_brushed = None;
+                model.edits.start_stroke();
+                paint(model);
+            }
+            if model.selecting {
+                model.selection = Some((model.cursor_cell, model.cursor_cell));
+            }
+            if model.pasting {
+                let cells = selection::translate(model.clipboard.clone(), model.cursor_cell);
+                let changed = model.edits.insert_cells(&mut *model.state, cells);
+                edited(model, changed);
             }
         },
         MouseInput {
             state: Released,
             button: Left,
             ..
-        } => model.clicked = false,
+        } => {
+            model.clicked = false;
+            model.edits.end_stroke();
+        }
         MouseInput {
             state: Pressed,
             button: Right,
             ..
         } => {
             model.drawing = !model.drawing;
+            model.selecting = false;
+            model.selection = None;
+            model.pasting = false;
             update_cursor_cell(model);
         }
         MouseWheel {
@@ -227,16 +701,29 @@ fn raw_window_event(app: &App, model: &mut Model, winit_event: &WindowEvent) {
         HoveredFile { .. } => model.hovering_file = true,
         DroppedFile(path) => {
             model.hovering_file = false;
-            model.state.insert_cells_rel(file::cells_from_file(path.as_path().to_str().unwrap().to_string()), model.view);
+            let pattern = match file::pattern_from_file(&path.to_string_lossy()) {
+                Ok(pattern) => pattern,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    return;
+                }
+            };
+            let cells = pattern.cells
+                .into_iter()
+                .map(|cell| relative_to_view(cell, model.view))
+                .collect();
+            let changed = model.edits.insert_cells(&mut *model.state, cells);
+            edited(model, changed);
         }
         HoveredFileCancelled => model.hovering_file = false,
+        ModifiersChanged(modifiers) => model.modifiers = *modifiers,
         _ => (),
     }
 }
 
 fn update(app: &App, model: &mut Model, _update: Update) {
     // Move view when clicked.
-    if model.clicked && !model.drawing {
+    if model.clicked && !model.drawing && !model.selecting && !model.pasting {
         model.view.0 -= app.mouse.x as f64 / 100.0 / model.scale;
         model.view.1 -= app.mouse.y as f64 / 100.0 / model.scale;
 
@@ -247,18 +734,41 @@ fn update(app: &App, model: &mut Model, _update: Update) {
             model.view.1 *= -1.0;
         }
 
+        // Moving past the edge of a wrapping universe comes back in on the
+        // other side.
+        let center = model.state.topology().wrap_point((-model.view.0, -model.view.1));
+        model.view = (-center.0, -center.1);
+
         update_cursor_cell(model);
     }
 
-    // Update cells if enough time has passed.
-    if model.last_update.elapsed() >= Duration::from_millis(25) && !model.paused && model.state.count_cells() != 0 {
+    // Update cells for every tick that has passed since the last update, for as
+    // long as the update budget allows.
+    if model.paused || model.state.count_cells() == 0 {
         model.last_update = Instant::now();
-        model.state.tick();
+        return;
+    }
 
-        if model.drawing && model.clicked {
-            model.state.insert_cell(model.cursor_cell);
+    let update_start = Instant::now();
+    let mut advanced = false;
+    while update_start.elapsed() < UPDATE_BUDGET && model.state.count_cells() != 0 {
+        match model.speed.interval() {
+            Some(interval) if model.last_update.elapsed() < interval => break,
+            Some(interval) => model.last_update += interval,
+            None => model.last_update = Instant::now(),
         }
-   }
+        advance(model);
+        advanced = true;
+    }
+
+    // Ticks that did not fit in the budget are skipped, instead of piling up.
+    if model.speed.interval().is_some_and(|interval| model.last_update.elapsed() >= interval) {
+        model.last_update = Instant::now();
+    }
+
+    if advanced && model.drawing && model.clicked {
+        paint(model);
+    }
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
@@ -281,24 +791,105 @@ fn view(app: &App, model: &Model, frame: Frame) {
     );
     let (screen_bottom, screen_top) = (
         ((corner.y() - frame.rect().h()) as f64 / model.scale - (model.view.1)) as i32 - 2,
-        (((corner.y() as f64) / model.scale - (model.view.1))) as i32 + 2
+        ((corner.y() as f64) / model.scale - (model.view.1)) as i32 + 2
     );
 
     draw.background().color(background_color);
 
-    let tris = model.state.get_tris(
-        model.view,
-        cell_color,
-        screen_left,
-        screen_right,
-        screen_top,
-        screen_bottom,
-    );
-    let rendered = tris.len() / 2;
+    let on_screen = |cell: &Cell| {
+        cell.0 > screen_left && cell.0 < screen_right && cell.1 > screen_bottom && cell.1 < screen_top
+    };
+    let generation = model.state.generation();
+
+    if let (true, Some(activity)) = (model.show_heat_map, model.state.activity()) {
+        let max_changes = activity.max_changes();
+        let heat_tris = activity
+            .iter()
+            .filter(|(cell, _)| on_screen(cell))
+            .flat_map(|(cell, changes)| {
+                let [red, green, blue] = activity::heat_color(*changes, max_changes);
+                let color = Rgb::from_components((red as f32 / 255.0, green as f32 / 255.0, blue as f32 / 255.0));
+                cell_tris(cell, model.view, color)
+            });
+        draw.scale(model.scale as f32)
+            .mesh()
+            .tris_colored(heat_tris);
+    }
+
+    if let (Coloring::Trail, Some(ages)) = (model.coloring, model.state.ages()) {
+        let (from, to): (Rgb, Rgb) = (cell_color, background_color);
+        let trail_tris = ages
+            .trail()
+            .filter(|(cell, _)| on_screen(cell))
+            .flat_map(|(cell, died)| {
+                // Trails start out halfway between the cell and background
+                // colors, and fade into the background.
+                let fade = 0.5 + 0.5 * generation.saturating_sub(*died) as f32 / TRAIL_LENGTH as f32;
+                let color = Rgb::from_components((
+                    from.red + (to.red.max(0.0) - from.red) * fade,
+                    from.green + (to.green.max(0.0) - from.green) * fade,
+                    from.blue + (to.blue.max(0.0) - from.blue) * fade,
+                ));
+                cell_tris(cell, model.view, color)
+            });
+        draw.scale(model.scale as f32)
+            .mesh()
+            .tris_colored(trail_tris);
+    }
+
+    let rendered = match (model.coloring, model.state.ages()) {
+        (Coloring::Age, Some(ages)) => {
+            let tris: Vec<_> = ages
+                .living()
+                .filter(|(cell, _)| on_screen(cell))
+                .flat_map(|(cell, born)| cell_tris(cell, model.view, age_color(generation.saturating_sub(*born))))
+                .collect();
+            let rendered = tris.len() / 2;
 
-    draw.scale(model.scale as f32)
-        .mesh()
-        .tris_colored(tris);
+            draw.scale(model.scale as f32)
+                .mesh()
+                .tris_colored(tris);
+            rendered
+        }
+        _ => {
+            let tris = model.state.get_tris(
+                model.view,
+                cell_color,
+                screen_left,
+                screen_right,
+                screen_top,
+                screen_bottom,
+            );
+            let rendered = tris.len() / 2;
+
+            draw.scale(model.scale as f32)
+                .mesh()
+                .tris_colored(tris);
+            rendered
+        }
+    };
+
+    if let Some((min, max)) = model.state.topology().bounds() {
+        let (left, bottom) = (
+            (min.0 as f64 + model.view.0 - 0.5) as f32,
+            (min.1 as f64 + model.view.1 - 0.5) as f32
+        );
+        let (right, top) = (
+            (max.0 as f64 + model.view.0 + 0.5) as f32,
+            (max.1 as f64 + model.view.1 + 0.5) as f32
+        );
+        let border_points: [((_, _), _); 5] = [
+            ((left, bottom), cell_color),
+            ((left, top), cell_color),
+            ((right, top), cell_color),
+            ((right, bottom), cell_color),
+            ((left, bottom), cell_color),
+        ];
+        draw.scale(model.scale as f32)
+            .polyline()
+            .weight(0.2)
+            .points_colored(border_points);
+    }
 
     if model.hovering_file {
         let points: [((_, _), _); 5] = [
@@ -313,7 +904,7 @@ fn view(app: &App, model: &Model, frame: Frame) {
             .points_colored(points);
     }
 
-    let (x, y) = model.cursor_cell.into();
+    let (x, y) = model.cursor_cell;
     let (cursor_x, cursor_y) = (x as f64 + model.view.0 - 0.5, y as f64 + model.view.1 - 0.5);
     let (cursor_x, cursor_y) = (cursor_x as f32, cursor_y as f32);
     if model.drawing {
@@ -329,8 +920,50 @@ fn view(app: &App, model: &Model, frame: Frame) {
             .polyline()
             .weight(0.1 + (app.time * 2.5).sin().abs() / 15.0)
             .points_colored(cell_color_points);
+
+        draw.text(model.brush.name())
+            .x((cursor_x + 1.5) * model.scale as f32 + 100.0)
+            .y((cursor_y + 1.5) * model.scale as f32)
+            .color(cell_color)
+            .left_justify();
     }
     
+    if let Some((corner, other_corner)) = model.selection {
+        let (min, max) = selection::bounds(corner, other_corner);
+        let (left, bottom) = (
+            (min.0 as f64 + model.view.0 - 0.5) as f32,
+            (min.1 as f64 + model.view.1 - 0.5) as f32
+        );
+        let (right, top) = (
+            (max.0 as f64 + model.view.0 + 0.5) as f32,
+            (max.1 as f64 + model.view.1 + 0.5) as f32
+        );
+        let selection_points: [((_, _), _); 5] = [
+            ((left, bottom), cell_color),
+            ((left, top), cell_color),
+            ((right, top), cell_color),
+            ((right, bottom), cell_color),
+            ((left, bottom), cell_color),
+        ];
+        draw.scale(model.scale as f32)
+            .polyline()
+            .weight(0.1)
+            .points_colored(selection_points);
+    }
+
+    if model.pasting {
+        let ghost_color = Rgb::from_components((0.5, 0.5, 0.5));
+        let ghost_tris = model.clipboard
+            .iter()
+            .flat_map(|cell| {
+                let cell = (cell.0 + model.cursor_cell.0, cell.1 + model.cursor_cell.1);
+                cell_tris(&cell, model.view, ghost_color)
+            });
+        draw.scale(model.scale as f32)
+            .mesh()
+            .tris_colored(ghost_tris);
+    }
+
     let coordinates = format!("{}, {}", (-model.view.0) as i32, (-model.view.1) as i32);
     let cursor = format!("{}, {}", model.cursor_cell.0, model.cursor_cell.1);
 
@@ -390,37 +1023,100 @@ fn view(app: &App, model: &Model, frame: Frame) {
             .color(cell_color)
             .left_justify();
 
+        draw.text("Coloring:")
+            .x(corner.x() + 100.0)
+            .y(corner.y() - 102.5)
+            .color(cell_color)
+            .left_justify();
+        let coloring = match (model.coloring, model.state.ages()) {
+            (Coloring::Plain, _) | (_, Some(_)) => model.coloring.name().to_string(),
+            _ => format!("{} (not kept by this engine)", model.coloring.name()),
+        };
+        draw.text(&coloring)
+            .x(corner.x() + 100.0)
+            .y(corner.y() - 112.5)
+            .color(cell_color)
+            .left_justify();
+
+        draw.text("Rule:")
+            .x(corner.x() + 100.0)
+            .y(corner.y() - 122.5)
+            .color(cell_color)
+            .left_justify();
+        draw.text(&model.state.rule().to_string())
+            .x(corner.x() + 100.0)
+            .y(corner.y() - 132.5)
+            .color(cell_color)
+            .left_justify();
+
+        draw.text("Universe:")
+            .x(corner.x() + 100.0)
+            .y(corner.y() - 142.5)
+            .color(cell_color)
+            .left_justify();
+        draw.text(&model.state.topology().to_string())
+            .x(corner.x() + 100.0)
+            .y(corner.y() - 152.5)
+            .color(cell_color)
+            .left_justify();
+
+        draw.text("Behavior:")
+            .x(corner.x() + 100.0)
+            .y(corner.y() - 162.5)
+            .color(cell_color)
+            .left_justify();
+        let behavior = match model.behavior {
+            Some(behavior) => behavior.to_string(),
+            _ => "Unknown".to_string(),
+        };
+        draw.text(&behavior)
+            .x(corner.x() + 100.0)
+            .y(corner.y() - 172.5)
+            .color(cell_color)
+            .left_justify();
+
+        draw.text("Speed:")
+            .x(corner.x() + 100.0)
+            .y(corner.y() - 182.5)
+            .color(cell_color)
+            .left_justify();
+        draw.text(&model.speed.to_string())
+            .x(corner.x() + 100.0)
+            .y(corner.y() - 192.5)
+            .color(cell_color)
+            .left_justify();
+
+        draw.text("Edits:")
+            .x(corner.x() + 100.0)
+            .y(corner.y() - 202.5)
+            .color(cell_color)
+            .left_justify();
+        let edits = format!("{} to undo, {} to redo", model.edits.undo_amount(), model.edits.redo_amount());
+        draw.text(&edits)
+            .x(corner.x() + 100.0)
+            .y(corner.y() - 212.5)
+            .color(cell_color)
+            .left_justify();
+
         let status = match model.paused {
             true => "Paused",
             _ => "Running"
         };
+        let status = match model.recorder.as_ref() {
+            Some(recorder) => format!("{}, recording {} frames", status, recorder.frame_count()),
+            None => status.to_string(),
+        };
 
-        draw.text(status)
+        draw.text(&status)
             .x(corner.x() + 100.0)
-            .y(corner.y() - 102.5)
+            .y(corner.y() - 222.5)
             .color(cell_color)
             .left_justify();
     }
 
-    draw.to_frame(app, &frame).unwrap();
-}
-
-fn from_cells_to_bytes(collection: Vec<Cell>) -> Vec<u8> {
-    let mut bytes = Vec::default();
-
-    for cell in collection {
-        let x_bytes = cell.0.to_le_bytes();
-        bytes.push(x_bytes[0]);
-        bytes.push(x_bytes[1]);
-        bytes.push(x_bytes[2]);
-        bytes.push(x_bytes[3]);
-        
-        let y_bytes = cell.1.to_le_bytes();
-        bytes.push(y_bytes[0]);
-        bytes.push(y_bytes[1]);
-        bytes.push(y_bytes[2]);
-        bytes.push(y_bytes[3]);
+    if model.show_graph {
+        model.population_graph.draw(&draw, frame.rect(), cell_color);
     }
 
-    bytes
+    draw.to_frame(app, &frame).unwrap();
 }
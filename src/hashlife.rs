@@ -0,0 +1,623 @@
+use fxhash::FxHashMap as HashMap;
+use crate::state::*;
+use crate::rule::Rule;
+use crate::speed;
+use crate::topology::Topology;
+#[cfg(feature = "gui")]
+use nannou::prelude::geom::Tri;
+use rand::Rng;
+#[cfg(feature = "gui")]
+use std::collections::LinkedList;
+
+type NodeId = u32;
+
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+
+// Smallest root level, big enough to always have a center to advance.
+const MIN_LEVEL: u8 = 3;
+
+// Level of a root as wide as the universe, 2^32 cells, which goes around at
+// its edges.
+const UNIVERSE_LEVEL: u8 = 32;
+
+// Amount of nodes allowed before unreachable nodes are thrown away.
+const NODE_LIMIT: usize = 1 << 22;
+
+// A square of 2^level by 2^level cells, made of four squares one level below.
+// Level 0 nodes are single cells. Children are ordered nw, ne, sw, se, where
+// north is towards lower y, and west is towards lower x.
+struct Node {
+    children: [NodeId; 4],
+    level: u8,
+    population: u64,
+}
+
+pub struct HashLifeState {
+    nodes: Vec<Node>,
+    lookup: HashMap<[NodeId; 4], NodeId>,
+    results: HashMap<(NodeId, u8), NodeId>,
+    empty: Vec<NodeId>,
+    root: NodeId,
+    generation: usize,
+    rule: Rule,
+}
+
+pub fn hashlife_state() -> HashLifeState {
+    let nodes = vec![
+        Node { children: [DEAD; 4], level: 0, population: 0 },
+        Node { children: [DEAD; 4], level: 0, population: 1 },
+    ];
+
+    let lookup = HashMap::default();
+    let results = HashMap::default();
+    let empty = vec![DEAD];
+
+    let generation: usize = 0;
+
+    let rule = Rule::default();
+
+    let mut state = HashLifeState {
+        nodes,
+        lookup,
+        results,
+        empty,
+        root: DEAD,
+        generation,
+        rule,
+    };
+    state.root = state.empty(MIN_LEVEL);
+
+    state
+}
+
+impl HashLifeState {
+    fn level(&self, node: NodeId) -> u8 {
+        self.nodes[node as usize].level
+    }
+
+    fn population(&self, node: NodeId) -> u64 {
+        self.nodes[node as usize].population
+    }
+
+    fn children(&self, node: NodeId) -> [NodeId; 4] {
+        self.nodes[node as usize].children
+    }
+
+    // Returns the canonical node with the given children, creating it if needed.
+    fn join(&mut self, nw: NodeId, ne: NodeId, sw: NodeId, se: NodeId) -> NodeId {
+        let children = [nw, ne, sw, se];
+        if let Some(&node) = self.lookup.get(&children) {
+            return node;
+        }
+
+        let node = self.nodes.len() as NodeId;
+        self.nodes.push(Node {
+            children,
+            level: self.level(nw) + 1,
+            population: children.iter().map(|&child| self.population(child)).sum(),
+        });
+        self.lookup.insert(children, node);
+
+        node
+    }
+
+    fn empty(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let below = *self.empty.last().unwrap();
+            let node = self.join(below, below, below, below);
+            self.empty.push(node);
+        }
+        self.empty[level as usize]
+    }
+
+    // Distance from the center of the root to its edges.
+    fn half_size(&self) -> i64 {
+        1 << (self.level(self.root) - 1)
+    }
+
+    // Doubles the width of the root, keeping the same center.
+    fn expand(&mut self) {
+        let [nw, ne, sw, se] = self.children(self.root);
+        let empty = self.empty(self.level(self.root) - 1);
+
+        let nw = self.join(empty, empty, empty, nw);
+        let ne = self.join(empty, empty, ne, empty);
+        let sw = self.join(empty, sw, empty, empty);
+        let se = self.join(se, empty, empty, empty);
+        self.root = self.join(nw, ne, sw, se);
+    }
+
+    // Doubles the width of a root as wide as the universe, keeping the same
+    // center. Beyond its edges the universe starts over, so the root is filled
+    // with copies of it, each shifted by half its width to line up.
+    fn expand_around(&mut self) {
+        let [nw, ne, sw, se] = self.children(self.root);
+        let shifted = self.join(se, sw, ne, nw);
+        self.root = self.join(shifted, shifted, shifted, shifted);
+    }
+
+    // Whether all live cells of the root are within its center quarter.
+    fn is_centered(&self) -> bool {
+        let [nw, ne, sw, se] = self.children(self.root);
+        let inner = self.population(self.children(nw)[3])
+            + self.population(self.children(ne)[2])
+            + self.population(self.children(sw)[1])
+            + self.population(self.children(se)[0]);
+
+        inner == self.population(self.root)
+    }
+
+    fn center(&mut self, node: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.children(node);
+        self.join(
+            self.children(nw)[3],
+            self.children(ne)[2],
+            self.children(sw)[1],
+            self.children(se)[0],
+        )
+    }
+
+    // Halves the width of the root while no live cells are lost.
+    fn shrink(&mut self) {
+        while self.level(self.root) > MIN_LEVEL && self.is_centered() {
+            self.root = self.center(self.root);
+        }
+    }
+
+    // Returns the node with the cell at (x, y) set, where (x, y) is relative to
+    // the north west corner of the node.
+    fn set(&mut self, node: NodeId, x: i64, y: i64, alive: bool) -> NodeId {
+        let level = self.level(node);
+        if level == 0 {
+            return if alive { ALIVE } else { DEAD };
+        }
+
+        let half = 1 << (level - 1);
+        let mut children = self.children(node);
+        let index = (x >= half) as usize + 2 * (y >= half) as usize;
+        children[index] = self.set(children[index], x % half, y % half, alive);
+
+        self.join(children[0], children[1], children[2], children[3])
+    }
+
+    fn get(&self, node: NodeId, x: i64, y: i64) -> bool {
+        let level = self.level(node);
+        if level == 0 {
+            return node == ALIVE;
+        }
+
+        let half = 1 << (level - 1);
+        let index = (x >= half) as usize + 2 * (y >= half) as usize;
+        self.get(self.children(node)[index], x % half, y % half)
+    }
+
+    fn set_cell(&mut self, cell: Cell, alive: bool) {
+        let (x, y) = (cell.0 as i64, cell.1 as i64);
+        while x < -self.half_size() || x >= self.half_size() || y < -self.half_size() || y >= self.half_size() {
+            // Cells outside of the root are already dead.
+            if !alive {
+                return;
+            }
+            self.expand();
+        }
+
+        let half_size = self.half_size();
+        self.root = self.set(self.root, x + half_size, y + half_size, alive);
+    }
+
+    fn cell_alive(&self, cell: Cell) -> bool {
+        let (x, y) = (cell.0 as i64, cell.1 as i64);
+        let half_size = self.half_size();
+        if x < -half_size || x >= half_size || y < -half_size || y >= half_size {
+            return false;
+        }
+
+        self.get(self.root, x + half_size, y + half_size)
+    }
+
+    // Advances the level 2 node by one generation, returning its level 1 center.
+    fn advance_base(&mut self, node: NodeId) -> NodeId {
+        let mut grid = [[false; 4]; 4];
+        for (y, row) in grid.iter_mut().enumerate() {
+            for (x, alive) in row.iter_mut().enumerate() {
+                *alive = self.get(node, x as i64, y as i64);
+            }
+        }
+
+        let mut center = [DEAD; 4];
+        for (index, result) in center.iter_mut().enumerate() {
+            let (x, y) = (1 + index % 2, 1 + index / 2);
+            let mut neighbor_count = 0;
+            for (neighbor_x, neighbor_y) in [
+                (x - 1, y - 1), (x, y - 1), (x + 1, y - 1),
+                (x - 1, y), (x + 1, y),
+                (x - 1, y + 1), (x, y + 1), (x + 1, y + 1),
+            ] {
+                neighbor_count += grid[neighbor_y][neighbor_x] as u8;
+            }
+
+            let alive = match grid[y][x] {
+                true => self.rule.survives(neighbor_count),
+                false => self.rule.born(neighbor_count),
+            };
+            if alive {
+                *result = ALIVE;
+            }
+        }
+
+        self.join(center[0], center[1], center[2], center[3])
+    }
+
+    // Returns the center of the node, advanced by 2^exponent generations. The
+    // exponent is capped at level - 2, which is as far as the node can see.
+    fn successor(&mut self, node: NodeId, exponent: u8) -> NodeId {
+        let level = self.level(node);
+        if self.population(node) == 0 {
+            return self.empty(level - 1);
+        }
+
+        let exponent = exponent.min(level - 2);
+        if let Some(&result) = self.results.get(&(node, exponent)) {
+            return result;
+        }
+
+        let result = if level == 2 {
+            self.advance_base(node)
+        } else {
+            let [nw, ne, sw, se] = self.children(node);
+            let [_, nw_ne, nw_sw, nw_se] = self.children(nw);
+            let [ne_nw, _, ne_sw, ne_se] = self.children(ne);
+            let [sw_nw, sw_ne, _, sw_se] = self.children(sw);
+            let [se_nw, se_ne, se_sw, _] = self.children(se);
+
+            // Nine overlapping squares, each half the width of the node.
+            let parts = [
+                nw,
+                self.join(nw_ne, ne_nw, nw_se, ne_sw),
+                ne,
+                self.join(nw_sw, nw_se, sw_nw, sw_ne),
+                self.join(nw_se, ne_sw, sw_ne, se_nw),
+                self.join(ne_sw, ne_se, se_nw, se_ne),
+                sw,
+                self.join(sw_ne, se_nw, sw_se, se_sw),
+                se,
+            ];
+
+            if exponent == level - 2 {
+                // Advance halfway with the nine squares, and the rest with the
+                // four squares put together from their results.
+                let mut steps = [DEAD; 9];
+                for (step, part) in steps.iter_mut().zip(parts) {
+                    *step = self.successor(part, exponent);
+                }
+
+                let mut quarters = [DEAD; 4];
+                for (index, quarter) in quarters.iter_mut().enumerate() {
+                    let corner = index % 2 + 3 * (index / 2);
+                    let square = self.join(
+                        steps[corner],
+                        steps[corner + 1],
+                        steps[corner + 3],
+                        steps[corner + 4],
+                    );
+                    *quarter = self.successor(square, exponent);
+                }
+
+                self.join(quarters[0], quarters[1], quarters[2], quarters[3])
+            } else {
+                // Advance only the four squares put together from the centers
+                // of the nine squares.
+                let mut centers = [DEAD; 9];
+                for (center, part) in centers.iter_mut().zip(parts) {
+                    *center = self.center(part);
+                }
+
+                let mut quarters = [DEAD; 4];
+                for (index, quarter) in quarters.iter_mut().enumerate() {
+                    let corner = index % 2 + 3 * (index / 2);
+                    let square = self.join(
+                        centers[corner],
+                        centers[corner + 1],
+                        centers[corner + 3],
+                        centers[corner + 4],
+                    );
+                    *quarter = self.successor(square, exponent);
+                }
+
+                self.join(quarters[0], quarters[1], quarters[2], quarters[3])
+            }
+        };
+
+        self.results.insert((node, exponent), result);
+
+        result
+    }
+
+    // Rebuilds the node storage with only the nodes reachable from the root.
+    fn collect_garbage(&mut self) {
+        let mut nodes = vec![
+            Node { children: [DEAD; 4], level: 0, population: 0 },
+            Node { children: [DEAD; 4], level: 0, population: 1 },
+        ];
+        let mut lookup = HashMap::default();
+        let mut moved: HashMap<NodeId, NodeId> = HashMap::default();
+        moved.insert(DEAD, DEAD);
+        moved.insert(ALIVE, ALIVE);
+
+        fn copy(
+            old: &[Node],
+            node: NodeId,
+            nodes: &mut Vec<Node>,
+            lookup: &mut HashMap<[NodeId; 4], NodeId>,
+            moved: &mut HashMap<NodeId, NodeId>,
+        ) -> NodeId {
+            if let Some(&new_node) = moved.get(&node) {
+                return new_node;
+            }
+
+            let mut children = old[node as usize].children;
+            for child in children.iter_mut() {
+                *child = copy(old, *child, nodes, lookup, moved);
+            }
+
+            let new_node = nodes.len() as NodeId;
+            nodes.push(Node {
+                children,
+                level: old[node as usize].level,
+                population: old[node as usize].population,
+            });
+            lookup.insert(children, new_node);
+            moved.insert(node, new_node);
+
+            new_node
+        }
+
+        self.root = copy(&self.nodes, self.root, &mut nodes, &mut lookup, &mut moved);
+        self.nodes = nodes;
+        self.lookup = lookup;
+        self.results.clear();
+        self.empty.truncate(1);
+    }
+
+    fn collect(&self, node: NodeId, x: i64, y: i64, collection: &mut Vec<Cell>) {
+        if self.population(node) == 0 {
+            return;
+        }
+
+        let level = self.level(node);
+        if level == 0 {
+            collection.push((x as i32, y as i32));
+            return;
+        }
+
+        let half = 1 << (level - 1);
+        let [nw, ne, sw, se] = self.children(node);
+        self.collect(nw, x, y, collection);
+        self.collect(ne, x + half, y, collection);
+        self.collect(sw, x, y + half, collection);
+        self.collect(se, x + half, y + half, collection);
+    }
+
+    // Collects the live cells of the node that are strictly within the bounds.
+    fn collect_within(
+        &self,
+        node: NodeId,
+        x: i64,
+        y: i64,
+        bounds: (i64, i64, i64, i64),
+        collection: &mut Vec<Cell>,
+    ) {
+        let (left, right, bottom, top) = bounds;
+        let size = 1 << self.level(node);
+        if self.population(node) == 0 || x + size <= left + 1 || x >= right || y + size <= bottom + 1 || y >= top {
+            return;
+        }
+
+        if size == 1 {
+            collection.push((x as i32, y as i32));
+            return;
+        }
+
+        let half = size / 2;
+        let [nw, ne, sw, se] = self.children(node);
+        self.collect_within(nw, x, y, bounds, collection);
+        self.collect_within(ne, x + half, y, bounds, collection);
+        self.collect_within(sw, x, y + half, bounds, collection);
+        self.collect_within(se, x + half, y + half, bounds, collection);
+    }
+}
+
+impl State for HashLifeState {
+    fn tick(&mut self) {
+        self.step(0);
+    }
+
+    fn step(&mut self, exponent: u32) {
+        if self.population(self.root) == 0 {
+            return;
+        }
+
+        if self.nodes.len() > NODE_LIMIT {
+            self.collect_garbage();
+        }
+
+        // Leaps wider than the universe are made in several.
+        if exponent >= UNIVERSE_LEVEL as u32 {
+            self.step(exponent - 1);
+            self.step(exponent - 1);
+            return;
+        }
+
+        // Make room for the pattern to grow by up to 2^exponent cells in every
+        // direction, since the successor only covers the center of the root.
+        // There is no more room than the whole universe, where the cells that
+        // leave on one side come back on the other.
+        while self.level(self.root) < UNIVERSE_LEVEL
+            && ((self.level(self.root) as u32) < exponent + 2 || !self.is_centered())
+        {
+            self.expand();
+        }
+        match self.level(self.root) {
+            UNIVERSE_LEVEL => self.expand_around(),
+            _ => self.expand(),
+        }
+
+        self.root = self.successor(self.root, exponent as u8);
+        self.shrink();
+
+        self.generation += 1 << exponent;
+    }
+
+    fn max_step_exponent(&self) -> u32 {
+        speed::MAX_STEP_EXPONENT
+    }
+
+    fn insert_cells(&mut self, mut collection: Vec<Cell>) {
+        for cell in collection.drain(0..) {
+            self.set_cell(cell, true);
+        }
+    }
+
+    fn insert_cells_rel(&mut self, mut collection: Vec<Cell>, view: (f64, f64)) {
+        for cell in collection.drain(0..) {
+            self.set_cell(relative_to_view(cell, view), true);
+        }
+    }
+
+    fn insert_cell(&mut self, cell: Cell) {
+        self.set_cell(cell, true);
+    }
+
+    fn remove_cell(&mut self, cell: Cell) {
+        self.set_cell(cell, false);
+    }
+
+    fn toggle_cell(&mut self, cell: Cell) {
+        let alive = self.cell_alive(cell);
+        self.set_cell(cell, !alive);
+    }
+
+    fn is_alive(&self, cell: Cell) -> bool {
+        self.cell_alive(cell)
+    }
+
+    fn remove_cells(&mut self, mut collection: Vec<Cell>) {
+        for cell in collection.drain(0..) {
+            self.set_cell(cell, false);
+        }
+    }
+
+    fn collect_cells(&self) -> Vec<Cell> {
+        let mut collection = Vec::default();
+        let half_size = self.half_size();
+
+        self.collect(self.root, -half_size, -half_size, &mut collection);
+
+        collection
+    }
+
+    fn cells_in_rect(&self, min: Cell, max: Cell) -> Vec<Cell> {
+        let mut collection = Vec::default();
+        let half_size = self.half_size();
+
+        self.collect_within(
+            self.root,
+            -half_size,
+            -half_size,
+            (min.0 as i64 - 1, max.0 as i64 + 1, min.1 as i64 - 1, max.1 as i64 + 1),
+            &mut collection,
+        );
+
+        collection
+    }
+
+    fn count_cells(&self) -> usize {
+        self.population(self.root) as usize
+    }
+
+    fn random_cell(&self) -> Cell {
+        let mut node = self.root;
+        let (mut x, mut y) = (-self.half_size(), -self.half_size());
+
+        // Walk down towards the cell with a random index among the live ones.
+        let mut index = rand::thread_rng().gen_range(0..self.population(node));
+        while self.level(node) > 0 {
+            let half = 1 << (self.level(node) - 1);
+            for (quadrant, child) in self.children(node).into_iter().enumerate() {
+                if index < self.population(child) {
+                    node = child;
+                    x += half * (quadrant % 2) as i64;
+                    y += half * (quadrant / 2) as i64;
+                    break;
+                }
+                index -= self.population(child);
+            }
+        }
+
+        (x as i32, y as i32)
+    }
+
+    fn generation(&self) -> usize {
+        self.generation
+    }
+
+    fn set_generation(&mut self, generation: usize) {
+        self.generation = generation;
+    }
+
+    fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+        self.results.clear();
+    }
+
+    fn topology(&self) -> Topology {
+        Topology::Unbounded
+    }
+
+    // The quadtree only knows how to grow, so bounded universes are left to
+    // the other engines.
+    fn set_topology(&mut self, topology: Topology) -> Result<(), String> {
+        match topology {
+            Topology::Unbounded => Ok(()),
+            _ => Err(format!("the hashlife engine only supports the unbounded universe, not '{}'", topology)),
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    fn get_tris(
+        &self,
+        view: (f64, f64),
+        cell_color: nannou::prelude::rgb::Rgb,
+        screen_left: i32,
+        screen_right: i32,
+        screen_top: i32,
+        screen_bottom: i32
+    ) -> LinkedList<Tri<([f32; 3], nannou::prelude::rgb::Rgb)>> {
+        let mut tri_list = LinkedList::default();
+        let mut visible = Vec::default();
+        let half_size = self.half_size();
+
+        self.collect_within(
+            self.root,
+            -half_size,
+            -half_size,
+            (screen_left as i64, screen_right as i64, screen_bottom as i64, screen_top as i64),
+            &mut visible,
+        );
+
+        for cell in visible.iter() {
+            let [first_tri, second_tri] = cell_tris(cell, view, cell_color);
+
+            tri_list.push_front(first_tri);
+            tri_list.push_front(second_tri);
+        }
+
+        tri_list
+    }
+}
+
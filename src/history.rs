@@ -0,0 +1,164 @@
+use fxhash::FxHashSet as HashSet;
+use std::collections::VecDeque;
+use std::mem::size_of;
+use crate::state::{Cell, State};
+
+// A way back to an earlier generation: the cells born and killed when
+// advancing from it. Going back only undoes those, so cells drawn in the
+// meantime are left as they are.
+struct Entry {
+    generation: usize,
+    births: Vec<Cell>,
+    deaths: Vec<Cell>,
+}
+
+impl Entry {
+    fn memory(&self) -> usize {
+        size_of::<Entry>() + (self.births.len() + self.deaths.len()) * size_of::<Cell>()
+    }
+}
+
+// Past generations of the cells, dropping the oldest ones once the memory
+// budget (in bytes) is used up.
+pub struct History {
+    entries: VecDeque<Entry>,
+    memory: usize,
+    budget: usize,
+}
+
+pub fn history(budget: usize) -> History {
+    History {
+        entries: VecDeque::new(),
+        memory: 0,
+        budget,
+    }
+}
+
+impl History {
+    // Advances the cells by 2^exponent generations, remembering the way back.
+    pub fn advance(&mut self, state: &mut dyn State, exponent: u32) {
+        if self.budget == 0 {
+            state.step(exponent);
+            return;
+        }
+
+        if state.last_changes().is_some() {
+            for _ in 0..1usize << exponent {
+                let generation = state.generation();
+                state.tick();
+
+                let changes = state.last_changes().unwrap();
+                self.push(Entry {
+                    generation,
+                    births: changes.births.to_vec(),
+                    deaths: changes.deaths.to_vec(),
+                });
+            }
+        } else if state.count_cells() * size_of::<Cell>() <= self.budget {
+            // The changes are found by comparing the cells before and after.
+            let generation = state.generation();
+            let before: HashSet<Cell> = state.collect_cells().into_iter().collect();
+            state.step(exponent);
+            let after: HashSet<Cell> = state.collect_cells().into_iter().collect();
+
+            self.push(Entry {
+                generation,
+                births: after.difference(&before).copied().collect(),
+                deaths: before.difference(&after).copied().collect(),
+            });
+        } else {
+            // Too many cells to compare, so the way back is lost.
+            self.clear();
+            state.step(exponent);
+        }
+    }
+
+    fn push(&mut self, entry: Entry) {
+        self.memory += entry.memory();
+        self.entries.push_back(entry);
+
+        while self.memory > self.budget {
+            match self.entries.pop_front() {
+                Some(entry) => self.memory -= entry.memory(),
+                None => break,
+            }
+        }
+    }
+
+    // Goes back to the generation before the last advance. Returns false if
+    // there is nothing to go back to.
+    pub fn step_back(&mut self, state: &mut dyn State) -> bool {
+        let Some(entry) = self.entries.pop_back() else {
+            return false;
+        };
+        self.memory -= entry.memory();
+
+        state.remove_cells(entry.births);
+        state.insert_cells(entry.deaths);
+        state.set_generation(entry.generation);
+
+        true
+    }
+
+    // Goes back the amount of generations, or as far back as remembered.
+    pub fn rewind(&mut self, state: &mut dyn State, generations: usize) {
+        let target = state.generation().saturating_sub(generations);
+        while state.generation() > target && self.step_back(state) {}
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.memory = 0;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+
+    fn sorted(mut cells: Vec<Cell>) -> Vec<Cell> {
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn stepping_back_keeps_drawn_cells_on_every_engine() {
+        let glider = vec![(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let drawn = (100, 100);
+
+        for engine in Engine::ALL {
+            let mut state = engine.build(2);
+            let mut history = history(1 << 20);
+            state.insert_cells(glider.clone());
+
+            history.advance(&mut *state, 0);
+            history.advance(&mut *state, 0);
+            state.insert_cell(drawn);
+            assert!(history.step_back(&mut *state), "{}", engine);
+            assert_eq!(state.generation(), 1, "{}", engine);
+
+            // HashLife leaps ahead at once, the others a generation at a time.
+            history.advance(&mut *state, 3);
+            history.rewind(&mut *state, 100);
+            assert_eq!(state.generation(), 0, "{}", engine);
+
+            let mut expected = glider.clone();
+            expected.push(drawn);
+            assert_eq!(sorted(state.collect_cells()), sorted(expected), "{}", engine);
+            assert!(!history.step_back(&mut *state), "{}", engine);
+        }
+    }
+
+    #[test]
+    fn nothing_is_remembered_without_a_budget() {
+        let mut state = Engine::Single.build(1);
+        let mut history = history(0);
+        state.insert_cells(vec![(0, 0), (1, 0), (2, 0)]);
+
+        history.advance(&mut *state, 0);
+        assert_eq!(state.generation(), 1);
+        assert!(!history.step_back(&mut *state));
+    }
+}
@@ -0,0 +1,48 @@
+// The engines and file formats of gol, usable without the GUI. The GUI is only
+// built with the "gui" feature, which is on by default.
+
+#[cfg(feature = "gui")]
+#[macro_use]
+extern crate lazy_static;
+
+pub mod active;
+pub mod activity;
+pub mod ages;
+pub mod analysis;
+pub mod bytes;
+pub mod edits;
+pub mod engine;
+pub mod file;
+pub mod hashlife;
+pub mod history;
+pub mod parallel;
+pub mod recording;
+pub mod rle;
+pub mod rule;
+pub mod selection;
+pub mod session;
+pub mod single;
+pub mod soup;
+pub mod speed;
+pub mod state;
+pub mod telemetry;
+pub mod tiles;
+pub mod topology;
+
+#[cfg(feature = "gui")]
+pub mod graph;
+#[cfg(feature = "gui")]
+pub mod gui;
+
+pub use active::{active_state, ActiveState};
+pub use bytes::{from_bytes_to_cells, from_cells_to_bytes};
+pub use engine::Engine;
+pub use file::{cells_from_file, pattern_from_file};
+pub use hashlife::{hashlife_state, HashLifeState};
+pub use parallel::{parallel_state, ParallelState};
+pub use rle::Pattern;
+pub use rule::Rule;
+pub use single::{single_state, SingleState};
+pub use state::{Cell, State};
+pub use tiles::{tile_state, TileState};
+pub use topology::Topology;
@@ -1,33 +1,30 @@
-use std::thread;
-use std::io::{self, Read};
+use std::fs;
+use std::path::Path;
+use std::io::{self, Read, Write};
 use std::time::Instant;
-use nannou::rand::rand::prelude::StdRng;
-use nannou::rand::{SeedableRng, RngCore};
-use crate::state::Cell;
+use rand::rngs::StdRng;
+use rand::{SeedableRng, RngCore};
+use gol::state::{self, Cell};
+use gol::rule::Rule;
+use gol::topology::Topology;
+use gol::rle::{self, Pattern};
+use gol::session::{self, Session};
+use gol::soup::{self, Soup};
+use gol::speed;
+use gol::bytes::{from_bytes_to_cells, from_cells_to_bytes};
+use gol::recording::{self, Recorder};
+use gol::telemetry::{self, StatsWriter};
+use gol::engine::Engine;
+use gol::{analysis, engine, file};
+#[cfg(feature = "gui")]
+use gol::gui;
 
-use clap::{Arg, ArgAction, Command};
-
-mod parallel;
-mod single;
-mod state;
-mod gui;
-mod file;
-
-#[macro_use]
-extern crate lazy_static;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use clap::parser::ValueSource;
 
 fn main() {
     let mut start_cells: Vec<Cell> = Vec::default();
 
-    // let mut r = StdRng::seed_from_u64(0);
-    // for _ in 0..1000000 {
-    //     let cell = (
-    //         r.next_u32() as i32 % 1000,
-    //         r.next_u32() as i32 % 1000
-    //     );
-    //     start_cells.push(cell);
-    // }
-
     let matches = Command::new("gol")
         .version("0.1.1")
         .about("A simple Conway's Game of Life implementation")
@@ -39,11 +36,32 @@ fn main() {
             "| `+` `-` (or scrollwheel) | Zoom in or out               |",
             "| `tab`                    | Toggle stats                 |",
             "| `c`                      | Toggle dark mode             |",
+            "| `l`                      | Toggle population graph      |",
+            "| `g`                      | Toggle recording to a GIF    |",
+            "| `i`                      | Save a screenshot to a PNG   |",
             "| `space`                  | Toggle pause                 |",
             "| `t`                      | Advance cells by one tick    |",
+            "| `[` `]`                  | Slow down or speed up ticks  |",
+            "| `,` `.`                  | Shrink or grow the step size |",
+            "| `backspace`              | Step back one generation     |",
+            "| `b`                      | Rewind several generations   |",
+            "| `e`                      | Switch drawing brush         |",
+            "| `m`                      | Switch coloring of cells     |",
+            "| `w`                      | Toggle activity heat map     |",
+            "| `s`                      | Toggle selection mode        |",
+            "| `y` `x` `delete`         | Copy, cut or delete selected |",
+            "| `p`                      | Toggle pasting mode          |",
+            "| `n`                      | Drop a random soup at cursor |",
+            "| `r`                      | Rotate copied cells          |",
+            "| `f` `v`                  | Flip copied cells            |",
             "| `h`                      | Jump back home, to (0, 0)    |",
             "| `j`                      | Jump to random live cell     |",
             "| `z`                      | Undo last jump               |",
+            "| `ctrl+z`                 | Undo last edit               |",
+            "| `ctrl+y` `ctrl+shift+z`  | Redo last undone edit        |",
+            "| `1` to `9`               | Jump to bookmark             |",
+            "| `ctrl+1` to `ctrl+9`     | Bookmark the view            |",
+            "| `ctrl+s` `ctrl+o`        | Save or load the session     |",
         ].join("\n"))
         .arg(
             Arg::new("benchmark")
@@ -52,12 +70,70 @@ fn main() {
                 .help("Perform benchmark")
                 .value_parser(clap::value_parser!(u32))
         )
+        .arg(
+            Arg::new("benchmark-all")
+                .long("benchmark-all")
+                .value_name("PASSES")
+                .help("Benchmark every engine on the same cells, check that they agree, and compare them")
+                .value_parser(clap::value_parser!(u32))
+                .num_args(0..=1)
+                .default_missing_value("1")
+        )
+        .arg(
+            Arg::new("updates")
+                .long("updates")
+                .value_name("N")
+                .help("Amount of updates each benchmark pass runs")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("500")
+        )
+        .arg(
+            Arg::new("cells")
+                .long("cells")
+                .value_name("N")
+                .help("Amount of cells placed at random for benchmarks")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1000000")
+        )
+        .arg(
+            Arg::new("area")
+                .long("area")
+                .value_name("WIDTHxHEIGHT")
+                .help("Area the benchmark cells are placed in")
+                .value_parser(soup::parse_size)
+                .default_value("1000x1000")
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .value_name("N")
+                .help("Threads the parallel engine ticks with, using the single engine with 1 [default: GOL_THREADS, or every thread available]")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .global(true)
+        )
+        .arg(
+            Arg::new("warmup")
+                .long("warmup")
+                .value_name("N")
+                .help("Amount of benchmark passes to run and leave out before timing")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("0")
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("FORMAT")
+                .help("Format of benchmark results: text, or json for a JSON object per engine on each line")
+                .value_parser(["text", "json"])
+                .default_value("text")
+        )
         .arg(
             Arg::new("input-bytes")
                 .short('i')
                 .long("input-bytes")
                 .help("Build cells from bytes read from stdin")
                 .action(ArgAction::SetTrue)
+                .global(true)
         )
         .arg(
             Arg::new("output-bytes")
@@ -66,14 +142,289 @@ fn main() {
                 .help("Output bytes of cells to stdout")
                 .action(ArgAction::SetTrue)
         )
+        .arg(
+            Arg::new("rule")
+                .short('r')
+                .long("rule")
+                .help("Rule to simulate, in B/S notation (e.g. B36/S23)")
+                .value_parser(clap::value_parser!(Rule))
+                .default_value("B3/S23")
+                .global(true)
+        )
+        .arg(
+            Arg::new("engine")
+                .long("engine")
+                .value_name("ENGINE")
+                .help("Engine to simulate with: single, parallel, hashlife, tiles or active [default: parallel, or single with one thread]")
+                .value_parser(clap::value_parser!(Engine))
+                .global(true)
+        )
+        .arg(
+            Arg::new("hashlife")
+                .long("hashlife")
+                .help("Simulate with the HashLife engine, same as --engine hashlife")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("engine")
+                .global(true)
+        )
+        .arg(
+            Arg::new("tiles")
+                .long("tiles")
+                .help("Simulate with the bit-packed tile engine, same as --engine tiles")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["engine", "hashlife"])
+                .hide(true)
+                .global(true)
+        )
+        .arg(
+            Arg::new("topology")
+                .short('t')
+                .long("topology")
+                .help("Shape of the universe: unbounded, or plane, torus, klein or cross with a size (e.g. torus:100x80)")
+                .value_parser(clap::value_parser!(Topology))
+                .default_value("unbounded")
+                .global(true)
+        )
+        .arg(
+            Arg::new("load")
+                .short('l')
+                .long("load")
+                .value_name("FILE")
+                .help("Load cells from an RLE or text pattern file")
+                .global(true)
+        )
+        .arg(
+            Arg::new("save")
+                .short('s')
+                .long("save")
+                .value_name("FILE")
+                .help("Save cells to an RLE file on exit")
+        )
+        .arg(
+            Arg::new("session")
+                .long("session")
+                .value_name("FILE")
+                .help("Resume the session in FILE if there is one, and save the session to it on exit")
+        )
+        .arg(
+            Arg::new("headless")
+                .long("headless")
+                .help("Run without the GUI, and write the resulting cells to stdout")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("generations")
+                .short('g')
+                .long("generations")
+                .value_name("N")
+                .help("Amount of generations to run in headless mode")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("0")
+        )
+        .arg(
+            Arg::new("record")
+                .long("record")
+                .value_name("FILE")
+                .help("Record the generations to an animated GIF, or an APNG if FILE ends with .png")
+        )
+        .arg(
+            Arg::new("record-area")
+                .long("record-area")
+                .value_name("WIDTHxHEIGHT[@X,Y]")
+                .help("Cells to record in headless mode, centered on (0, 0) or (X, Y) [default: all starting cells]")
+                .value_parser(recording::parse_area)
+        )
+        .arg(
+            Arg::new("record-from")
+                .long("record-from")
+                .value_name("N")
+                .help("First generation to record in headless mode")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("0")
+        )
+        .arg(
+            Arg::new("cell-size")
+                .long("cell-size")
+                .value_name("PIXELS")
+                .help("Width of each cell in recordings")
+                .value_parser(clap::value_parser!(u32).range(1..=64))
+                .default_value("4")
+        )
+        .arg(
+            Arg::new("cell-color")
+                .long("cell-color")
+                .value_name("HEX")
+                .help("Color of live cells in recordings")
+                .value_parser(recording::parse_color)
+                .default_value("ffffff")
+        )
+        .arg(
+            Arg::new("background-color")
+                .long("background-color")
+                .value_name("HEX")
+                .help("Color of dead cells in recordings")
+                .value_parser(recording::parse_color)
+                .default_value("000000")
+        )
+        .arg(
+            Arg::new("frame-delay")
+                .long("frame-delay")
+                .value_name("MS")
+                .help("Time each generation is shown in recordings")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("50")
+        )
+        .arg(
+            Arg::new("stats-out")
+                .long("stats-out")
+                .value_name("FILE")
+                .help("Write the population, births, deaths and bounding box of every generation to a CSV file, or JSON lines if FILE ends with .json or .jsonl")
+        )
+        .arg(
+            Arg::new("heat-map")
+                .long("heat-map")
+                .value_name("FILE")
+                .help("Count how many times each cell changes in headless mode, and write the counts to a PNG, or CSV unless FILE ends with .png")
+        )
+        .arg(
+            Arg::new("graph-length")
+                .long("graph-length")
+                .value_name("N")
+                .help("Amount of generations shown in the population graph in the GUI")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("500")
+        )
+        .arg(
+            Arg::new("history-budget")
+                .long("history-budget")
+                .value_name("MB")
+                .help("Memory to spend on remembering past generations in the GUI")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("256")
+        )
+        .arg(
+            Arg::new("rewind")
+                .long("rewind")
+                .value_name("N")
+                .help("Amount of generations to go back when rewinding in the GUI")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("100")
+        )
+        .arg(
+            Arg::new("speed")
+                .long("speed")
+                .value_name("TICKS")
+                .help("Ticks per second in the GUI, or 'max' to tick as often as possible")
+                .value_parser(speed::parse_ticks_per_second)
+                .default_value("40")
+        )
+        .arg(
+            Arg::new("step")
+                .long("step")
+                .value_name("EXPONENT")
+                .help("Advance 2^EXPONENT generations on each tick in the GUI")
+                .value_parser(clap::value_parser!(u32).range(0..=speed::MAX_STEP_EXPONENT as i64))
+                .default_value("0")
+        )
+        .arg(
+            Arg::new("soup")
+                .long("soup")
+                .value_name("WIDTHxHEIGHT")
+                .help("Start with a random soup of cells, centered on (0, 0), and drop soups of this size in the GUI")
+                .value_parser(soup::parse_size)
+                .global(true)
+        )
+        .arg(
+            Arg::new("density")
+                .long("density")
+                .help("Chance of each cell in a soup being alive")
+                .value_parser(soup::parse_density)
+                .default_value("0.5")
+                .global(true)
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .help("Seed of the random soups and benchmark cells")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("0")
+                .global(true)
+        )
+        .subcommand(
+            Command::new("analyze")
+                .about("Find out whether the cells are still, oscillating or moving")
+                .arg(
+                    Arg::new("max-generations")
+                        .short('m')
+                        .long("max-generations")
+                        .value_name("N")
+                        .help("Amount of generations to run before giving up")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10000")
+                )
+        )
         .get_matches();
 
+    // Threads given on the command line win over the environment variable.
+    let thread_amount = match matches.get_one::<u32>("threads") {
+        Some(threads) => *threads as usize,
+        None => engine::thread_amount().unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }),
+    };
+    let engine = match (matches.get_flag("hashlife"), matches.get_flag("tiles")) {
+        (true, _) => Engine::HashLife,
+        (_, true) => Engine::Tiles,
+        _ => matches.get_one::<Engine>("engine").copied().unwrap_or(Engine::auto(thread_amount)),
+    };
+    let mut topology = *matches.get_one::<Topology>("topology").unwrap();
+    let mut rule = *matches.get_one::<Rule>("rule").unwrap();
+
+    // A session picks up where it left off, unless the rule or the topology is
+    // given on the command line.
+    let session_path = matches.get_one::<String>("session").cloned();
+    let mut session = Session::default();
+    if let Some(path) = session_path.as_ref()
+        && Path::new(path).exists()
+    {
+        session = session::read(path).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        });
+        start_cells.append(&mut session.cells);
+        if matches.value_source("rule") == Some(ValueSource::DefaultValue) {
+            rule = session.rule;
+        }
+        if matches.value_source("topology") == Some(ValueSource::DefaultValue) {
+            topology = session.topology;
+        }
+    }
+
+    let workload = Workload {
+        updates: *matches.get_one::<usize>("updates").unwrap(),
+        cell_amount: *matches.get_one::<usize>("cells").unwrap(),
+        area: *matches.get_one::<(u32, u32)>("area").unwrap(),
+        seed: *matches.get_one::<u64>("seed").unwrap(),
+        thread_amount,
+    };
+    let warmup_passes = *matches.get_one::<u32>("warmup").unwrap();
+    let json = matches.get_one::<String>("format").unwrap() == "json";
+    if workload.cell_amount > 0 && (workload.area.0 == 0 || workload.area.1 == 0) {
+        eprintln!("the benchmark area must not be empty");
+        std::process::exit(1);
+    }
+
     if let Some(benchmark_passes) = matches.get_one::<u32>("benchmark") {
-        run_benchmark(*benchmark_passes);
+        run_benchmark(*benchmark_passes, engine, &workload, warmup_passes, json);
         return;
     } 
     else if matches.contains_id("benchmark") {
-        run_benchmark(1);
+        run_benchmark(1, engine, &workload, warmup_passes, json);
+        return;
+    }
+    if let Some(benchmark_passes) = matches.get_one::<u32>("benchmark-all") {
+        run_benchmark_all(*benchmark_passes, &workload, warmup_passes, json);
         return;
     }
 
@@ -84,17 +435,403 @@ fn main() {
     }
     let send_cells_to_stdout = matches.get_flag("output-bytes");
 
-    gui::run_gui(start_cells, send_cells_to_stdout);
+    if let Some(file_path) = matches.get_one::<String>("load") {
+        match file::pattern_from_file(file_path) {
+            Ok(mut pattern) => {
+                start_cells.append(&mut pattern.cells);
+
+                // A rule given on the command line wins over the one in the file.
+                if matches.value_source("rule") == Some(ValueSource::DefaultValue) {
+                    rule = pattern.rule.unwrap_or(rule);
+                }
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+    let save_path = matches.get_one::<String>("save").cloned();
+
+    let density = *matches.get_one::<f64>("density").unwrap();
+    let seed = *matches.get_one::<u64>("seed").unwrap();
+    if let Some(&(width, height)) = matches.get_one::<(u32, u32)>("soup") {
+        let soup = Soup { width, height, density, seed };
+        start_cells.append(&mut soup.cells((0, 0)));
+    }
+
+    let record_path = matches.get_one::<String>("record").cloned();
+    let record_style = recording::Style {
+        cell_size: *matches.get_one::<u32>("cell-size").unwrap(),
+        cell_color: *matches.get_one::<recording::Color>("cell-color").unwrap(),
+        background_color: *matches.get_one::<recording::Color>("background-color").unwrap(),
+        frame_delay: *matches.get_one::<u32>("frame-delay").unwrap(),
+    };
+
+    if let Some(analyze_matches) = matches.subcommand_matches("analyze") {
+        let max_generations = *analyze_matches.get_one::<usize>("max-generations").unwrap();
+        run_analysis(build_state(start_cells, rule, topology, engine, thread_amount), max_generations);
+        return;
+    }
+
+    if matches.get_flag("headless") {
+        let generations = *matches.get_one::<u64>("generations").unwrap();
+        let heat_map = matches.get_one::<String>("heat-map").map(|path| (path.clone(), record_style.cell_size));
+        if !engine.counts_changes() && heat_map.is_some() {
+            eprintln!("the {} engine cannot count how many times cells change", engine);
+            std::process::exit(1);
+        }
+
+        // Without an area, everything the cells start out in is recorded.
+        let recording = record_path.map(|path| {
+            let area = matches.get_one::<(Cell, Cell)>("record-area").copied().or_else(|| {
+                let left = start_cells.iter().map(|cell| cell.0).min()?;
+                let right = start_cells.iter().map(|cell| cell.0).max()?;
+                let bottom = start_cells.iter().map(|cell| cell.1).min()?;
+                let top = start_cells.iter().map(|cell| cell.1).max()?;
+                Some(((left, bottom), (right, top)))
+            });
+            let Some((min, max)) = area else {
+                eprintln!("there are no cells to record, choose an area with --record-area");
+                std::process::exit(1);
+            };
+
+            let record_from = *matches.get_one::<u64>("record-from").unwrap();
+            if record_from > generations {
+                eprintln!("--record-from {} is past the last generation, {}, so nothing would be recorded", record_from, generations);
+                std::process::exit(1);
+            }
+            let recorder = recording::recorder(path, min, max, record_style).unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            });
+            (recorder, record_from)
+        });
+
+        let stats_writer = stats_writer(&matches);
+        let mut state = build_state(start_cells, rule, topology, engine, thread_amount);
+        state.set_generation(session.generation);
+        let state = run_headless(
+            state,
+            generations,
+            send_cells_to_stdout,
+            save_path,
+            recording,
+            stats_writer,
+            heat_map,
+        );
+
+        if let Some(path) = session_path {
+            session.capture(&*state);
+            if let Err(error) = session::write(&path, &session) {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    #[cfg(feature = "gui")]
+    {
+        let history_budget = *matches.get_one::<usize>("history-budget").unwrap() * 1024 * 1024;
+        let rewind_amount = *matches.get_one::<usize>("rewind").unwrap();
+        let graph_length = *matches.get_one::<usize>("graph-length").unwrap();
+        let speed = speed::Speed {
+            ticks_per_second: *matches.get_one::<Option<u32>>("speed").unwrap(),
+            step_exponent: *matches.get_one::<u32>("step").unwrap(),
+        };
+
+        // Soups dropped in the GUI continue from the seed after the one used at start.
+        let (soup_width, soup_height) = matches.get_one::<(u32, u32)>("soup").copied().unwrap_or((64, 64));
+        let soup = Soup {
+            width: soup_width,
+            height: soup_height,
+            density,
+            seed: seed.wrapping_add(matches.contains_id("soup") as u64),
+        };
+
+        let stats_writer = stats_writer(&matches);
+        gui::run_gui(gui::Settings {
+            input_cells: start_cells,
+            send_cells_to_stdout,
+            rule,
+            topology,
+            engine,
+            thread_amount,
+            save_path,
+            history_budget,
+            rewind_amount,
+            soup,
+            speed,
+            record_path,
+            record_style,
+            stats_writer,
+            graph_length,
+            session_path,
+            session,
+        });
+    }
+
+    #[cfg(not(feature = "gui"))]
+    {
+        let _ = (start_cells, send_cells_to_stdout, save_path, seed, record_path, record_style, session_path, session);
+        eprintln!("gol was built without the GUI, run it with --headless, --benchmark or analyze instead");
+        std::process::exit(1);
+    }
+}
+
+// Only made once it is known to be written to, as it empties the file.
+fn stats_writer(matches: &ArgMatches) -> Option<StatsWriter> {
+    matches.get_one::<String>("stats-out").map(|path| {
+        telemetry::stats_writer(path.clone()).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        })
+    })
+}
+
+fn build_state(
+    start_cells: Vec<Cell>,
+    rule: Rule,
+    topology: Topology,
+    engine: Engine,
+    thread_amount: usize,
+) -> Box<dyn state::State> {
+    let mut state = engine.build(thread_amount);
+    state.set_rule(rule);
+    if let Err(error) = state.set_topology(topology) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+    state.insert_cells(start_cells);
+
+    state
+}
+
+fn run_headless(
+    mut state: Box<dyn state::State>,
+    generations: u64,
+    send_cells_to_stdout: bool,
+    save_path: Option<String>,
+    mut recording: Option<(Recorder, u64)>,
+    mut stats_writer: Option<StatsWriter>,
+    heat_map: Option<(String, u32)>,
+) -> Box<dyn state::State> {
+    state.set_activity_tracking(heat_map.is_some());
+
+    // Generations are only gone through one at a time once they need to be
+    // recorded or written down.
+    let first_observed = match (&recording, &stats_writer) {
+        (_, Some(_)) => 0,
+        (Some((_, record_from)), None) => *record_from,
+        (None, None) => generations,
+    };
+    leap(&mut *state, first_observed.min(generations));
+
+    for generation in first_observed..=generations {
+        if let Some((recorder, record_from)) = recording.as_mut()
+            && generation >= *record_from
+        {
+            recorder.capture(&*state);
+        }
+        if let Some(stats_writer) = stats_writer.as_mut()
+            && let Err(error) = stats_writer.write(&*state, generation > 0)
+        {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+
+        if generation < generations {
+            state.tick();
+        }
+    }
+
+    let finished = recording
+        .map_or(Ok(()), |(recorder, _)| recorder.finish())
+        .and(stats_writer.map_or(Ok(()), |mut stats_writer| stats_writer.flush()));
+    if let Err(error) = finished {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+
+    if let Some((path, cell_size)) = heat_map {
+        let written = match state.activity() {
+            Some(activity) => activity.write(&path, cell_size),
+            None => Err(format!("could not write '{}': the engine does not count the changes of the cells", path)),
+        };
+        if let Err(error) = written {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+
+    let pattern = Pattern {
+        cells: state.collect_cells(),
+        rule: Some(state.rule()),
+        ..Default::default()
+    };
+
+    if let Some(file_path) = save_path
+        && let Err(error) = fs::write(&file_path, rle::write(&pattern))
+    {
+        eprintln!("could not save '{}': {}", file_path, error);
+        std::process::exit(1);
+    }
+
+    let output = match send_cells_to_stdout {
+        true => from_cells_to_bytes(pattern.cells),
+        _ => rle::write(&pattern).into_bytes(),
+    };
+    let _ = io::stdout().lock().write_all(&output);
+
+    state
+}
+
+// Advance by the powers of two making up the amount of generations, which lets
+// HashLife take big leaps.
+fn leap(state: &mut dyn state::State, generations: u64) {
+    for exponent in (0..u64::BITS).rev() {
+        if generations & (1 << exponent) != 0 {
+            state.step(exponent);
+        }
+    }
+}
+
+fn run_analysis(mut state: Box<dyn state::State>, max_generations: usize) {
+    match analysis::analyze(&mut *state, max_generations) {
+        Some(behavior) => println!("{}", behavior),
+        None => println!("no repetition within {} generations", max_generations),
+    }
+}
+
+// What each benchmark pass runs.
+struct Workload {
+    updates: usize,
+    cell_amount: usize,
+    // Width and height of the area the cells are placed in, from (0, 0).
+    area: (u32, u32),
+    seed: u64,
+    // Threads of the parallel engine.
+    thread_amount: usize,
+}
+
+impl Workload {
+    // Places the cells at random, the same way each time.
+    fn cells(&self) -> Vec<Cell> {
+        let mut r = StdRng::seed_from_u64(self.seed);
+
+        let mut collection = Vec::default();
+        for _ in 0..self.cell_amount {
+            let cell = (
+                (r.next_u32() % self.area.0) as i32,
+                (r.next_u32() % self.area.1) as i32
+            );
+            collection.push(cell);
+        }
+
+        collection
+    }
+}
+
+// Runs the updates of the workload on a new state of the engine, returning how
+// long it took in milliseconds, along with the state.
+fn benchmark_pass(engine: Engine, workload: &Workload, cells: Vec<Cell>) -> (f64, Box<dyn state::State>) {
+    let mut state = engine.build(workload.thread_amount);
+    state.insert_cells(cells);
+
+    let begin_time = Instant::now();
+
+    for _ in 0..workload.updates {
+        state.tick();
+    }
+
+    (begin_time.elapsed().as_secs_f64() * 1000.0, state)
+}
+
+// Durations of benchmark passes, in milliseconds.
+struct Summary {
+    mean: f64,
+    min: f64,
+    median: f64,
+    p95: f64,
+    stddev: f64,
+}
+
+fn summarize(durations: &[f64]) -> Summary {
+    let mut sorted = durations.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let count = sorted.len() as f64;
+
+    let mean = sorted.iter().sum::<f64>() / count;
+    let median = match sorted.len() % 2 {
+        0 => (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2.0,
+        _ => sorted[sorted.len() / 2],
+    };
+    // The nearest rank, so the slowest pass is the 95th percentile of few passes.
+    let p95 = sorted[((0.95 * count).ceil() as usize).clamp(1, sorted.len()) - 1];
+    let variance = sorted.iter().map(|duration| (duration - mean).powi(2)).sum::<f64>() / count;
+
+    Summary {
+        mean,
+        min: sorted[0],
+        median,
+        p95,
+        stddev: variance.sqrt(),
+    }
+}
+
+// Writes the results of benchmarking an engine as a line of JSON.
+fn print_json(engine: Engine, workload: &Workload, warmup_passes: u32, durations: &[f64], population: usize) {
+    let summary = summarize(durations);
+    let durations: Vec<String> = durations.iter().map(|duration| format!("{:.3}", duration)).collect();
+    println!(
+        "{{\"engine\":\"{}\",\"updates\":{},\"cells\":{},\"area\":[{},{}],\"seed\":{},\"threads\":{},\"warmup\":{},\"passes\":{},\"population\":{},\"durations_ms\":[{}],\"mean_ms\":{:.3},\"min_ms\":{:.3},\"median_ms\":{:.3},\"p95_ms\":{:.3},\"stddev_ms\":{:.3}}}",
+        engine,
+        workload.updates,
+        workload.cell_amount,
+        workload.area.0,
+        workload.area.1,
+        workload.seed,
+        match engine {
+            Engine::Parallel => workload.thread_amount,
+            _ => 1,
+        },
+        warmup_passes,
+        durations.len(),
+        population,
+        durations.join(","),
+        summary.mean,
+        summary.min,
+        summary.median,
+        summary.p95,
+        summary.stddev,
+    );
 }
 
-fn run_benchmark(benchmark_passes: u32) {
+fn run_benchmark(benchmark_passes: u32, engine: Engine, workload: &Workload, warmup_passes: u32, json: bool) {
     let start_bench_time = Instant::now();
 
     let mut time_vec = Vec::new();
 
-    let updates_per_run = 500;
-    let cell_amount = 1000000;
-    let runs = benchmark_passes;
+    let updates_per_run = workload.updates;
+    let collection = workload.cells();
+    let cell_amount = collection.len();
+    let runs = benchmark_passes.max(1);
+
+    for _ in 0..warmup_passes {
+        benchmark_pass(engine, workload, collection.clone());
+    }
+
+    if json {
+        let mut population = 0;
+        for _ in 0..runs {
+            let (duration, state) = benchmark_pass(engine, workload, collection.clone());
+            time_vec.push(duration);
+            population = state.count_cells();
+        }
+        print_json(engine, workload, warmup_passes, &time_vec, population);
+        return;
+    }
 
     let label_string = format!(
         "   Running {} updates on {} cells, {} time(s) ↴   ",
@@ -108,81 +845,108 @@ fn run_benchmark(benchmark_passes: u32) {
     let progress_string = format!("0 out of {}", runs);
     eprint!("{: ^width$}\r", progress_string, width = line_len);
     for i in 0..runs {
-        let mut state = state::state();
-
-        let mut collection = Vec::default();
-        let mut r = StdRng::seed_from_u64(0);
-
-        for _ in 0..cell_amount {
-            let cell = (
-                r.next_u32() as i32 % 1000,
-                r.next_u32() as i32 % 1000
-            );
-            collection.push(cell);
-        }
-        state.insert_cells(collection);
-
-        let begin_time = Instant::now();
-
-        for _ in 0..updates_per_run {
-            state.tick();
-        }
-
-        time_vec.push((Instant::now().duration_since(begin_time)).as_millis() as f32);
+        let (duration, _) = benchmark_pass(engine, workload, collection.clone());
+        time_vec.push(duration);
 
         //eprint!("{} out of {}\r", i, runs);
         let progress_string = format!("{} out of {}", i, runs);
         eprint!("{:^width$}\r", progress_string, width = line_len);
     }
     
-    let runtime = time_vec.iter().sum::<f32>() / time_vec.len() as f32;
+    let summary = summarize(&time_vec);
+    let runtime = summary.mean;
     println!(
         "Total runtime: {:>width$}  s",
         (Instant::now().duration_since(start_bench_time)).as_millis() as f32 / 1000.0,
         width = line_len - 18
     );
     println!(
-        "Average run duration: {:>width$} ms",
+        "Average run duration: {:>width$.3} ms",
         runtime,
         width = line_len - 25
     );
     println!(
-        "Average tick duration: {:>width$} ms",
-        (runtime / updates_per_run as f32),
+        "Median run duration: {:>width$.3} ms",
+        summary.median,
+        width = line_len - 24
+    );
+    println!(
+        "Average tick duration: {:>width$.3} ms",
+        (runtime / updates_per_run.max(1) as f64),
         width = line_len - 26
     );
-    match thread::available_parallelism() {
-        Ok(i) => println!("Thread count: {:>width$}   ", i, width = line_len - 17),
-        Err(_) => println!("No multithreading"),
+    println!("Engine: {:>width$}   ", engine, width = line_len - 11);
+    if engine == Engine::Parallel {
+        println!("Thread count: {:>width$}   ", workload.thread_amount, width = line_len - 17);
     }
 }
 
-fn from_bytes_to_cells(bytes: Vec<u8>) -> Vec<Cell> {
-    let cell_amount = (bytes.len() - (bytes.len() % 8)) / 8;
-    let mut collection = Vec::default();
-
-    if cell_amount > 0 {
-        for i in 0..cell_amount {
-            let i = i * 8;
-            let cell_x = i32::from_le_bytes([
-                bytes[i], 
-                bytes[i+1], 
-                bytes[i+2], 
-                bytes[i+3],
-            ]);
-
-            let cell_y = i32::from_le_bytes([
-                bytes[i+4], 
-                bytes[i+5], 
-                bytes[i+6], 
-                bytes[i+7],
-            ]);
-
-            collection.push((cell_x, cell_y));
+// Runs the same benchmark on every engine, making sure they all end up with
+// the same cells, and compares their times to the single threaded engine.
+fn run_benchmark_all(benchmark_passes: u32, workload: &Workload, warmup_passes: u32, json: bool) {
+    let collection = workload.cells();
+    let runs = benchmark_passes.max(1);
+    if !json {
+        println!(
+            "Running {} updates on {} cells with every engine, {} time(s)",
+            workload.updates, collection.len(), runs
+        );
+    }
+
+    let mut expected_cells: Option<Vec<Cell>> = None;
+    let mut results = Vec::new();
+    for engine in Engine::ALL {
+        for _ in 0..warmup_passes {
+            benchmark_pass(engine, workload, collection.clone());
+        }
+
+        let mut time_vec = Vec::new();
+        for i in 0..runs {
+            eprint!("{}: {} out of {}\r", engine, i, runs);
+            let (duration, state) = benchmark_pass(engine, workload, collection.clone());
+            time_vec.push(duration);
+
+            let mut cells = state.collect_cells();
+            cells.sort_unstable();
+            match expected_cells.as_ref() {
+                Some(expected_cells) if *expected_cells != cells => {
+                    eprintln!(
+                        "the {} engine ended up with {} cells, where the {} engine ended up with {} other cells",
+                        engine, cells.len(), Engine::ALL[0], expected_cells.len()
+                    );
+                    std::process::exit(1);
+                }
+                Some(_) => (),
+                None => expected_cells = Some(cells),
+            }
         }
+        eprint!("{}\r", " ".repeat(40));
+
+        results.push((engine, time_vec));
+    }
+
+    let population = expected_cells.map_or(0, |cells| cells.len());
+    if json {
+        for (engine, time_vec) in results.iter() {
+            print_json(*engine, workload, warmup_passes, time_vec, population);
+        }
+        return;
     }
 
-    collection
+    println!("Every engine ended up with the same {} cells.", population);
+    println!();
+    println!("| Engine     | Average Time | Compared to `single` |");
+    println!("| :--------- | :----------- | :------------------- |");
+    let baseline = summarize(&results[0].1).mean;
+    for (engine, time_vec) in results {
+        let runtime = summarize(&time_vec).mean;
+        println!(
+            "| {:<10} | {:<12} | {:<20.3} |",
+            format!("`{}`", engine),
+            format!("{:.2} ms", runtime),
+            baseline / runtime.max(f64::MIN_POSITIVE),
+        );
+    }
 }
 
 //
@@ -2,27 +2,51 @@ use std::thread;
 use std::sync::{Arc, Mutex, RwLock};
 use threadpool::ThreadPool;
 use fxhash::FxHashSet as HashSet;
+use crate::activity::{self, Activity};
+use crate::ages::{self, CellAges};
 use crate::state::*;
+use crate::rule::Rule;
+use crate::topology::Topology;
+#[cfg(feature = "gui")]
 use nannou::prelude::geom::Tri;
+#[cfg(feature = "gui")]
 use nannou::color::Rgb;
-use nannou::rand::random_range;
+use rand::Rng;
+#[cfg(feature = "gui")]
 use std::collections::LinkedList;
 
+#[cfg(feature = "gui")]
+type TriList = LinkedList<Tri<([f32; 3], Rgb)>>;
+
 pub struct ParallelState {
     cells: Arc<RwLock<HashSet<Cell>>>,
     thread_amount: usize,
     kill_lists: Arc<Vec<Mutex<Vec<Cell>>>>,
     res_lists: Arc<Vec<Mutex<Vec<Cell>>>>,
-    tri_lists: Arc<Vec<Mutex<LinkedList<Tri<([f32; 3], Rgb)>>>>>,
+    #[cfg(feature = "gui")]
+    tri_lists: Arc<Vec<Mutex<TriList>>>,
+    #[cfg(feature = "gui")]
     cells_vec: Arc<RwLock<Vec<Cell>>>,
     workers: ThreadPool,
+    births: Vec<Cell>,
+    deaths: Vec<Cell>,
     generation: usize,
+    rule: Rule,
+    topology: Topology,
+    ages: Option<CellAges>,
+    activity: Option<Activity>,
 }
 
 pub fn parallel_state() -> ParallelState {
+    parallel_state_with_threads(thread::available_parallelism().map_or(1, |threads| threads.get()))
+}
+
+// Ticks with the amount of threads, counting the one ticking. With a single
+// thread, the ticking thread does all of the work.
+pub fn parallel_state_with_threads(thread_amount: usize) -> ParallelState {
     let cells = Arc::new(RwLock::new(HashSet::default()));
 
-    let thread_amount = thread::available_parallelism().unwrap().get();
+    let thread_amount = thread_amount.max(1);
     
     let kill_lists = Arc::new(
         (0..thread_amount)
@@ -36,29 +60,47 @@ pub fn parallel_state() -> ParallelState {
             .collect::<Vec<_>>(),
     );
 
+    #[cfg(feature = "gui")]
     let tri_lists = Arc::new(
         (0..thread_amount)
             .map(|_| Mutex::new(LinkedList::new()))
             .collect::<Vec<_>>(),
     );
 
+    #[cfg(feature = "gui")]
     let cells_vec = Arc::new(RwLock::new(Vec::default()));
     
+    // A pool needs at least one worker, even if it is never given work.
     let workers = ThreadPool::new(
-        thread::available_parallelism().unwrap().get() - 1
+        (thread_amount - 1).max(1)
     );
 
+    let births: Vec<Cell> = Vec::new();
+    let deaths: Vec<Cell> = Vec::new();
+
     let generation: usize = 0;
 
+    let rule = Rule::default();
+
+    let topology = Topology::default();
+
     ParallelState {
         cells,
         thread_amount,
         kill_lists,
         res_lists,
+        #[cfg(feature = "gui")]
         tri_lists,
+        #[cfg(feature = "gui")]
         cells_vec,
         workers,
+        births,
+        deaths,
         generation,
+        rule,
+        topology,
+        ages: None,
+        activity: None,
     }
 }
 
@@ -66,7 +108,7 @@ impl State for ParallelState {
     fn tick(&mut self) {
         let cells_vec = Arc::new(RwLock::new(self.cells.read().unwrap().iter().copied().collect::<Vec<_>>()));
         let cell_amount = cells_vec.read().unwrap().len();
-        let thread_distribution = Arc::new((cell_amount / self.thread_amount) as usize);
+        let thread_distribution = Arc::new(cell_amount / self.thread_amount);
 
         // Worker threads
         for thread_number in 0..self.thread_amount - 1 {
@@ -76,6 +118,8 @@ impl State for ParallelState {
             let thread_kill_lists = Arc::clone(&self.kill_lists);
             let thread_res_lists = Arc::clone(&self.res_lists);
             let this_thread_distribution = Arc::clone(&thread_distribution);
+            let rule = self.rule;
+            let topology = self.topology;
 
             self.workers.execute(move || {
                 let slice_start = thread_number * *this_thread_distribution;
@@ -85,20 +129,20 @@ impl State for ParallelState {
                 let mut res_list = thread_res_lists[thread_number].lock().unwrap();
                 
                 for cell in slice {
-                    let neighbors: [Cell; 8] = get_neighbors(&cell);
+                    let neighbors: [Cell; 8] = get_neighbors(cell, topology);
                     let neighbor_count = count_living_neighbors(&neighbors, &cells);
-                    if neighbor_count < 2 || neighbor_count > 3 {
+                    if !rule.survives(neighbor_count) {
                         kill_list.push(*cell);
                     }
 
                     // Iterate through dead neighbors, mark ones deserving for life.
                     for neighbor in neighbors
                         .iter()
-                        .filter(|&&neighbor| !cells.contains(&neighbor))
+                        .filter(|&&neighbor| !cells.contains(&neighbor) && topology.contains(&neighbor))
                     {
-                        let neighbor_neighbors: [Cell; 8] = get_neighbors(&neighbor);
+                        let neighbor_neighbors: [Cell; 8] = get_neighbors(neighbor, topology);
                         let neighbor_count = count_living_neighbors(&neighbor_neighbors, &cells);
-                        if neighbor_count == 3 {
+                        if rule.born(neighbor_count) {
                             res_list.push(*neighbor);
                         }
                     }
@@ -112,6 +156,7 @@ impl State for ParallelState {
             let thread_cells_set = &self.cells;
             let thread_kill_lists = &self.kill_lists;
             let thread_res_lists = &self.res_lists;
+            let topology = self.topology;
             
             let slice_start = (self.thread_amount - 1) * *thread_distribution;
             let slice = &thread_cells.read().unwrap()[slice_start .. thread_cells.read().unwrap().len()];
@@ -121,20 +166,20 @@ impl State for ParallelState {
             let mut res_list = thread_res_lists[self.thread_amount - 1].lock().unwrap();
             
             for cell in slice {
-                let neighbors: [Cell; 8] = get_neighbors(&cell);
+                let neighbors: [Cell; 8] = get_neighbors(cell, topology);
                 let neighbor_count = count_living_neighbors(&neighbors, &cells);
-                if neighbor_count < 2 || neighbor_count > 3 {
+                if !self.rule.survives(neighbor_count) {
                     kill_list.push(*cell);
                 }
 
                 // Iterate through dead neighbors, mark ones deserving for life.
                 for neighbor in neighbors
                     .iter()
-                    .filter(|&&neighbor| !cells.contains(&neighbor))
+                    .filter(|&&neighbor| !cells.contains(&neighbor) && topology.contains(&neighbor))
                 {
-                    let neighbor_neighbors: [Cell; 8] = get_neighbors(&neighbor);
+                    let neighbor_neighbors: [Cell; 8] = get_neighbors(neighbor, topology);
                     let neighbor_count = count_living_neighbors(&neighbor_neighbors, &cells);
-                    if neighbor_count == 3 {
+                    if self.rule.born(neighbor_count) {
                         res_list.push(*neighbor);
                     }
                 }
@@ -145,27 +190,50 @@ impl State for ParallelState {
 
         self.workers.join();
 
+        self.births.clear();
+        self.deaths.clear();
+
         let mut cells = self.cells.write().unwrap();
         for kill_list in self.kill_lists.iter() {
             let mut kill_list = kill_list.lock().unwrap();
             for cell in kill_list.iter() {
-                cells.remove(&cell);
+                cells.remove(cell);
             }
-            kill_list.clear();
+            self.deaths.append(&mut kill_list);
         }
         for res_list in self.res_lists.iter() {
             let mut res_list = res_list.lock().unwrap();
             for resurrected_cell in res_list.drain(0..) {
-                cells.insert(resurrected_cell);
+                // Cells with several live neighbors are marked more than once.
+                if cells.insert(resurrected_cell) {
+                    self.births.push(resurrected_cell);
+                }
             }
+        }
+
+        if let Some(ages) = self.ages.as_mut() {
+            ages.update(self.generation, &self.births, &self.deaths);
+        }
+        if let Some(activity) = self.activity.as_mut() {
+            activity.update(&self.births, &self.deaths);
         }    
     }
 
+    fn last_changes(&self) -> Option<Changes<'_>> {
+        Some(Changes {
+            births: &self.births,
+            deaths: &self.deaths,
+        })
+    }
+
     fn insert_cells(&mut self, mut collection: Vec<Cell>) {
         let mut cells = self.cells.write().unwrap();
 
-        for cell in collection.drain(0..) {
+        for cell in collection.drain(0..).filter_map(|cell| self.topology.wrap(cell)) {
             cells.insert(cell);
+            if let Some(ages) = self.ages.as_mut() {
+                ages.insert(self.generation, cell);
+            }
         }
     }
 
@@ -173,12 +241,59 @@ impl State for ParallelState {
         let mut cells = self.cells.write().unwrap();
         
         for cell in collection.drain(0..) {
-            cells.insert((cell.0 + view.1.floor() as i32, cell.1 - view.0.floor() as i32));
+            let cell = relative_to_view(cell, view);
+            if let Some(cell) = self.topology.wrap(cell) {
+                cells.insert(cell);
+                if let Some(ages) = self.ages.as_mut() {
+                    ages.insert(self.generation, cell);
+                }
+            }
         }
     }
     
     fn insert_cell(&mut self, cell: Cell) {
-        self.cells.write().unwrap().insert(cell);
+        if let Some(cell) = self.topology.wrap(cell) {
+            self.cells.write().unwrap().insert(cell);
+            if let Some(ages) = self.ages.as_mut() {
+                ages.insert(self.generation, cell);
+            }
+        }
+    }
+
+    fn remove_cell(&mut self, cell: Cell) {
+        if let Some(cell) = self.topology.wrap(cell) {
+            self.cells.write().unwrap().remove(&cell);
+            if let Some(ages) = self.ages.as_mut() {
+                ages.remove(&cell);
+            }
+        }
+    }
+
+    fn toggle_cell(&mut self, cell: Cell) {
+        let Some(cell) = self.topology.wrap(cell) else {
+            return;
+        };
+
+        let alive = self.cells.read().unwrap().contains(&cell);
+        match alive {
+            true => self.remove_cell(cell),
+            _ => self.insert_cell(cell),
+        }
+    }
+
+    fn is_alive(&self, cell: Cell) -> bool {
+        self.topology.wrap(cell).is_some_and(|cell| self.cells.read().unwrap().contains(&cell))
+    }
+
+    fn remove_cells(&mut self, mut collection: Vec<Cell>) {
+        let mut cells = self.cells.write().unwrap();
+
+        for cell in collection.drain(0..).filter_map(|cell| self.topology.wrap(cell)) {
+            cells.remove(&cell);
+            if let Some(ages) = self.ages.as_mut() {
+                ages.remove(&cell);
+            }
+        }
     }
     
     fn collect_cells(&self) -> Vec<Cell> {
@@ -186,19 +301,29 @@ impl State for ParallelState {
         let mut collection = Vec::default();
 
         for cell in cells.iter() {
-            collection.push(cell.clone());
+            collection.push(*cell);
         }
         
         collection
     }
 
+    fn cells_in_rect(&self, min: Cell, max: Cell) -> Vec<Cell> {
+        self.cells
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|cell| in_rect(cell, min, max))
+            .copied()
+            .collect()
+    }
+
     fn count_cells(&self) -> usize {
         self.cells.read().unwrap().len()
     }
 
     fn random_cell(&self) -> Cell {
         let cells = self.cells.read().unwrap();
-        let random_index = random_range(0, cells.len());
+        let random_index = rand::thread_rng().gen_range(0..cells.len());
         *(cells.iter().nth(random_index).unwrap())
     }
 
@@ -206,6 +331,63 @@ impl State for ParallelState {
         self.generation
     }
 
+    fn set_generation(&mut self, generation: usize) {
+        self.generation = generation;
+    }
+
+    fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+    }
+
+    fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    fn set_topology(&mut self, topology: Topology) -> Result<(), String> {
+        let collection = self.collect_cells();
+        self.topology = topology;
+        self.cells.write().unwrap().clear();
+        if let Some(ages) = self.ages.as_mut() {
+            ages.clear();
+        }
+        if let Some(activity) = self.activity.as_mut() {
+            activity.clear();
+        }
+        self.insert_cells(collection);
+
+        Ok(())
+    }
+
+    fn set_age_tracking(&mut self, enabled: bool) {
+        self.ages = match enabled {
+            true => {
+                let mut ages = ages::cell_ages();
+                for cell in self.cells.read().unwrap().iter() {
+                    ages.insert(self.generation, *cell);
+                }
+                Some(ages)
+            }
+            _ => None,
+        };
+    }
+
+    fn ages(&self) -> Option<&CellAges> {
+        self.ages.as_ref()
+    }
+
+    fn set_activity_tracking(&mut self, enabled: bool) {
+        self.activity = enabled.then(activity::activity);
+    }
+
+    fn activity(&self) -> Option<&Activity> {
+        self.activity.as_ref()
+    }
+
+    #[cfg(feature = "gui")]
     fn get_tris(
         &self, 
         view: (f64, f64), 
@@ -230,7 +412,7 @@ impl State for ParallelState {
                 .copied()
         );
         let cell_amount = self.cells_vec.read().unwrap().len();
-        let thread_distribution = Arc::new((cell_amount / self.thread_amount) as usize);
+        let thread_distribution = Arc::new(cell_amount / self.thread_amount);
 
         // Worker threads
         for thread_number in 0..self.thread_amount - 1 {
@@ -244,19 +426,7 @@ impl State for ParallelState {
                 let mut tri_list = thread_tri_lists[thread_number].lock().unwrap();
                 
                 for cell in slice {
-                    let point = [(cell.0 as f64 + view.0 - 0.5) as f32, (cell.1 as f64 + view.1 - 0.5) as f32];
-
-                    let first_tri = nannou::prelude::geom::Tri([
-                        ([point[0], point[1], 0.0], cell_color),
-                        ([point[0] + 1.0, point[1], 0.0], cell_color),
-                        ([point[0] + 1.0, point[1] + 1.0, 0.0], cell_color)
-                    ]);
-
-                    let second_tri = nannou::prelude::geom::Tri([
-                        first_tri[0], 
-                        ([point[0], point[1] + 1.0, 0.0], cell_color), 
-                        first_tri[2]
-                    ]);
+                    let [first_tri, second_tri] = cell_tris(cell, view, cell_color);
                     
                     tri_list.push_front(first_tri);
                     tri_list.push_front(second_tri);
@@ -275,19 +445,7 @@ impl State for ParallelState {
             let mut tri_list = thread_tri_lists[self.thread_amount - 1].lock().unwrap();
             
             for cell in slice {
-                let point = [(cell.0 as f64 + view.0 - 0.5) as f32, (cell.1 as f64 + view.1 - 0.5) as f32];
-
-                let first_tri = nannou::prelude::geom::Tri([
-                    ([point[0], point[1], 0.0], cell_color),
-                    ([point[0] + 1.0, point[1], 0.0], cell_color),
-                    ([point[0] + 1.0, point[1] + 1.0, 0.0], cell_color)
-                ]);
-
-                let second_tri = nannou::prelude::geom::Tri([
-                    first_tri[0], 
-                    ([point[0], point[1] + 1.0, 0.0], cell_color), 
-                    first_tri[2]
-                ]);
+                let [first_tri, second_tri] = cell_tris(cell, view, cell_color);
                 
                 tri_list.push_front(first_tri);
                 tri_list.push_front(second_tri);
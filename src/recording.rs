@@ -0,0 +1,229 @@
+use std::fs::File;
+use std::io::BufWriter;
+use crate::state::{Cell, State};
+
+pub type Color = [u8; 3];
+
+// Most pixels in each frame of a recording, as every frame is kept in memory
+// until the recording is written.
+const PIXEL_LIMIT: u64 = 1 << 26;
+
+// How recordings look.
+#[derive(Clone, Copy)]
+pub struct Style {
+    // Width and height of each cell, in pixels.
+    pub cell_size: u32,
+    pub cell_color: Color,
+    pub background_color: Color,
+    // Time each generation is shown, in milliseconds.
+    pub frame_delay: u32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            cell_size: 4,
+            cell_color: [255, 255, 255],
+            background_color: [0, 0, 0],
+            frame_delay: 50,
+        }
+    }
+}
+
+// Records a rectangle of the universe over several generations, to be written
+// to an animated GIF, or an APNG if the path ends with ".png".
+pub struct Recorder {
+    path: String,
+    min: Cell,
+    max: Cell,
+    style: Style,
+    // One byte per cell, 1 if alive, row by row from the top.
+    frames: Vec<Vec<u8>>,
+}
+
+// Fails if the frames would be too big to record, before anything is.
+pub fn recorder(path: String, min: Cell, max: Cell, style: Style) -> Result<Recorder, String> {
+    let recorder = Recorder {
+        path,
+        min,
+        max,
+        style,
+        frames: Vec::new(),
+    };
+
+    let cell_size = style.cell_size as u64;
+    let width = (recorder.width() as u64).saturating_mul(cell_size);
+    let height = (recorder.height() as u64).saturating_mul(cell_size);
+    let is_png = recorder.path.to_lowercase().ends_with(".png");
+    if !is_png && (width > u16::MAX as u64 || height > u16::MAX as u64) {
+        return Err(format!(
+            "could not record to '{}': GIFs can be at most {} pixels wide and high, not {}x{}",
+            recorder.path, u16::MAX, width, height
+        ));
+    }
+    if width.saturating_mul(height) > PIXEL_LIMIT {
+        return Err(format!(
+            "could not record to '{}': frames can be at most {} pixels, not {}x{}",
+            recorder.path, PIXEL_LIMIT, width, height
+        ));
+    }
+
+    Ok(recorder)
+}
+
+impl Recorder {
+    fn width(&self) -> usize {
+        (self.max.0 as i64 - self.min.0 as i64 + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.max.1 as i64 - self.min.1 as i64 + 1) as usize
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    // Records the cells within the rectangle as the next frame.
+    pub fn capture(&mut self, state: &dyn State) {
+        let (width, height) = (self.width(), self.height());
+        let mut frame = vec![0; width * height];
+
+        // Higher cells are shown higher up, like in the GUI.
+        for cell in state.cells_in_rect(self.min, self.max) {
+            let x = (cell.0 as i64 - self.min.0 as i64) as usize;
+            let y = (self.max.1 as i64 - cell.1 as i64) as usize;
+            frame[y * width + x] = 1;
+        }
+
+        self.frames.push(frame);
+    }
+
+    // Scales a frame up to one palette index per pixel.
+    fn pixels(&self, frame: &[u8]) -> Vec<u8> {
+        let cell_size = self.style.cell_size as usize;
+        let mut pixels = Vec::with_capacity(frame.len() * cell_size * cell_size);
+
+        for row in frame.chunks(self.width()) {
+            let scaled_row: Vec<u8> = row
+                .iter()
+                .flat_map(|&alive| std::iter::repeat_n(alive, cell_size))
+                .collect();
+            for _ in 0..cell_size {
+                pixels.extend_from_slice(&scaled_row);
+            }
+        }
+
+        pixels
+    }
+
+    // Writes the recorded frames to the file.
+    pub fn finish(self) -> Result<(), String> {
+        if self.frames.is_empty() {
+            return Err(format!("nothing was recorded to '{}'", self.path));
+        }
+
+        let file = File::create(&self.path)
+            .map_err(|error| format!("could not create '{}': {}", self.path, error))?;
+        let writer = BufWriter::new(file);
+
+        let result = match self.path.to_lowercase().ends_with(".png") {
+            true => self.write_apng(writer),
+            _ => self.write_gif(writer),
+        };
+
+        result.map_err(|error| format!("could not write '{}': {}", self.path, error))
+    }
+
+    fn palette(&self) -> Vec<u8> {
+        [self.style.background_color, self.style.cell_color].concat()
+    }
+
+    fn write_gif(&self, writer: BufWriter<File>) -> Result<(), String> {
+        let cell_size = self.style.cell_size as usize;
+        let (width, height) = (self.width() * cell_size, self.height() * cell_size);
+
+        let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &self.palette())
+            .map_err(|error| error.to_string())?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(|error| error.to_string())?;
+
+        for frame in self.frames.iter() {
+            let mut gif_frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, &self.pixels(frame), None);
+            // GIF delays are in hundredths of a second.
+            gif_frame.delay = (self.style.frame_delay / 10).min(u16::MAX as u32) as u16;
+            encoder.write_frame(&gif_frame).map_err(|error| error.to_string())?;
+        }
+
+        Ok(())
+    }
+
+    fn write_apng(&self, writer: BufWriter<File>) -> Result<(), String> {
+        let cell_size = self.style.cell_size as usize;
+        let (width, height) = (self.width() * cell_size, self.height() * cell_size);
+
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(self.palette());
+        encoder.set_animated(self.frames.len() as u32, 0).map_err(|error| error.to_string())?;
+        encoder
+            .set_frame_delay(self.style.frame_delay.min(u16::MAX as u32) as u16, 1000)
+            .map_err(|error| error.to_string())?;
+
+        let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+        for frame in self.frames.iter() {
+            writer.write_image_data(&self.pixels(frame)).map_err(|error| error.to_string())?;
+        }
+        writer.finish().map_err(|error| error.to_string())
+    }
+}
+
+// Parses colors written as hex, like "ff8800" or "#ff8800".
+pub fn parse_color(color: &str) -> Result<Color, String> {
+    let hex = color.trim().trim_start_matches('#');
+    let error = format!("color '{}' should be written as hex, like ff8800", color);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(error);
+    }
+
+    let mut rgb = [0; 3];
+    for (index, channel) in rgb.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).map_err(|_| error.clone())?;
+    }
+
+    Ok(rgb)
+}
+
+// Parses areas written as "WIDTHxHEIGHT", centered on (0, 0), or as
+// "WIDTHxHEIGHT@X,Y" centered on (X, Y). Returns the lower left and upper
+// right cells of the area.
+pub fn parse_area(area: &str) -> Result<(Cell, Cell), String> {
+    let (size, center) = match area.split_once('@') {
+        Some((size, center)) => {
+            let (x, y) = center
+                .split_once(',')
+                .ok_or(format!("center '{}' should be written as X,Y", center))?;
+            match (x.trim().parse::<i32>(), y.trim().parse::<i32>()) {
+                (Ok(x), Ok(y)) => (size, (x, y)),
+                _ => return Err(format!("center '{}' should be written as X,Y", center)),
+            }
+        }
+        None => (area, (0, 0)),
+    };
+
+    let (width, height) = crate::soup::parse_size(size)?;
+    if width == 0 || height == 0 {
+        return Err(format!("area '{}' must not be empty", area));
+    }
+
+    let min = (
+        center.0.wrapping_sub((width / 2) as i32),
+        center.1.wrapping_sub((height / 2) as i32),
+    );
+    let max = (
+        min.0.wrapping_add(width as i32 - 1),
+        min.1.wrapping_add(height as i32 - 1),
+    );
+
+    Ok((min, max))
+}
@@ -0,0 +1,335 @@
+use std::cmp::Reverse;
+use crate::state::Cell;
+use crate::rule::Rule;
+
+// Longest line written in the body of an RLE file, as recommended by the format.
+const LINE_WIDTH: usize = 70;
+
+// Most live cells read from a pattern, so that a malformed file cannot use up
+// all memory.
+const CELL_LIMIT: usize = 1 << 26;
+
+// A cell configuration read from a pattern file. Rows of the file go downwards,
+// so row n is placed at y = -n, to keep patterns upright on screen.
+#[derive(Default)]
+pub struct Pattern {
+    pub cells: Vec<Cell>,
+    pub rule: Option<Rule>,
+    pub name: Option<String>,
+    pub comments: Vec<String>,
+}
+
+// Reads a pattern in Extended RLE format.
+// See: https://conwaylife.com/wiki/Run_Length_Encoded
+pub fn parse(contents: &str) -> Result<Pattern, String> {
+    let mut pattern = Pattern::default();
+    let mut offset: Cell = (0, 0);
+    // Width and height given in the header line.
+    let mut size: Option<(i64, i64)> = None;
+
+    let (mut col, mut row): (i64, i64) = (0, 0);
+    let mut run_count: Option<i64> = None;
+
+    'lines: for line in contents.lines() {
+        let line = line.trim();
+
+        let Some((width, height)) = size else {
+            if let Some(comment) = line.strip_prefix('#') {
+                let (kind, text) = comment.split_at(comment.chars().next().map_or(0, |c| c.len_utf8()));
+                let text = text.trim();
+                match kind {
+                    "N" => pattern.name = Some(text.to_string()),
+                    "C" | "c" if text.starts_with("XRLE") => {
+                        for field in text.split_whitespace().skip(1) {
+                            if let Some(position) = field.strip_prefix("Pos=") {
+                                offset = parse_pair(position, ',')?;
+                            }
+                        }
+                    }
+                    "C" | "c" | "O" => pattern.comments.push(text.to_string()),
+                    "P" | "R" => offset = parse_pair(text, ' ')?,
+                    "r" => pattern.rule = Some(parse_rule(text)?),
+                    _ => (),
+                }
+                continue;
+            }
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('x') {
+                let (mut width, mut height) = (None, None);
+                for field in line.split(',') {
+                    if let Some((key, value)) = field.split_once('=') {
+                        match key.trim() {
+                            "x" => width = Some(parse_dimension(value)?),
+                            "y" => height = Some(parse_dimension(value)?),
+                            "rule" => pattern.rule = Some(parse_rule(value)?),
+                            _ => (),
+                        }
+                    }
+                }
+                match (width, height) {
+                    (Some(width), Some(height)) => size = Some((width, height)),
+                    _ => return Err("missing 'x = .., y = ..' header line".to_string()),
+                }
+                continue;
+            }
+
+            return Err("missing 'x = .., y = ..' header line".to_string());
+        };
+
+        let beyond_size = || format!("pattern goes beyond its size of {}x{}", width, height);
+
+        for character in line.chars() {
+            match character {
+                '0'..='9' => {
+                    let digit = character.to_digit(10).unwrap() as i64;
+                    // No run is longer than the pattern is wide or high.
+                    run_count = Some(
+                        run_count
+                            .unwrap_or(0)
+                            .checked_mul(10)
+                            .and_then(|count| count.checked_add(digit))
+                            .filter(|count| *count <= width.max(height))
+                            .ok_or_else(beyond_size)?
+                    );
+                    continue;
+                }
+                'b' | '.' => {
+                    col += run_count.unwrap_or(1);
+                    if col > width {
+                        return Err(beyond_size());
+                    }
+                }
+                '$' => {
+                    row += run_count.unwrap_or(1);
+                    col = 0;
+                    if row > height {
+                        return Err(beyond_size());
+                    }
+                }
+                '!' => break 'lines,
+                'o' | 'A'..='Z' => {
+                    let run = run_count.unwrap_or(1);
+                    if col + run > width || row >= height {
+                        return Err(beyond_size());
+                    }
+                    if pattern.cells.len() + run as usize > CELL_LIMIT {
+                        return Err(format!("pattern has more than {} live cells", CELL_LIMIT));
+                    }
+                    for _ in 0..run {
+                        pattern.cells.push((
+                            offset.0.wrapping_add(col as i32),
+                            offset.1.wrapping_add(row as i32).wrapping_neg(),
+                        ));
+                        col += 1;
+                    }
+                }
+                // Multi-state prefixes, always followed by a state letter.
+                'p'..='y' => continue,
+                character if character.is_whitespace() => continue,
+                character => return Err(format!("unexpected character '{}' in pattern", character)),
+            }
+            run_count = None;
+        }
+    }
+
+    if size.is_none() {
+        return Err("missing 'x = .., y = ..' header line".to_string());
+    }
+
+    Ok(pattern)
+}
+
+// Sizes go up to the width of the universe, 2^32 cells.
+fn parse_dimension(text: &str) -> Result<i64, String> {
+    text.trim()
+        .parse::<i64>()
+        .ok()
+        .filter(|size| (0..=1 << 32).contains(size))
+        .ok_or(format!("'{}' is not a pattern size", text.trim()))
+}
+
+fn parse_pair(text: &str, separator: char) -> Result<Cell, String> {
+    let (x, y) = text
+        .trim()
+        .split_once(separator)
+        .ok_or(format!("'{}' is not a coordinate pair", text))?;
+
+    match (x.trim().parse(), y.trim().parse()) {
+        (Ok(x), Ok(y)) => Ok((x, y)),
+        _ => Err(format!("'{}' is not a coordinate pair", text)),
+    }
+}
+
+// Rules may carry a suffix describing the universe (e.g. "B3/S23:T100,100"),
+// which is left out.
+fn parse_rule(text: &str) -> Result<Rule, String> {
+    let text = text.trim();
+    text.split(':').next().unwrap_or(text).parse()
+}
+
+// Writes the pattern in Extended RLE format. The position of the pattern is
+// kept, so reading the output back gives the exact same cells.
+pub fn write(pattern: &Pattern) -> String {
+    let mut cells = pattern.cells.clone();
+    cells.sort_by_key(|cell| (Reverse(cell.1), cell.0));
+    cells.dedup();
+
+    let left = cells.iter().map(|cell| cell.0).min().unwrap_or(0);
+    let right = cells.iter().map(|cell| cell.0).max().unwrap_or(0);
+    let top = cells.iter().map(|cell| cell.1).max().unwrap_or(0);
+    let bottom = cells.iter().map(|cell| cell.1).min().unwrap_or(0);
+
+    // Runs of dead cells ('b'), live cells ('o') and line ends ('$').
+    let mut runs: Vec<(i64, char)> = Vec::default();
+    let mut push_run = |count: i64, tag: char| {
+        if count == 0 {
+            return;
+        }
+        match runs.last_mut() {
+            Some((last_count, last_tag)) if *last_tag == tag => *last_count += count,
+            _ => runs.push((count, tag)),
+        }
+    };
+
+    let (mut col, mut row) = (left as i64, top as i64);
+    for cell in cells.iter() {
+        if cell.1 as i64 != row {
+            push_run(row - cell.1 as i64, '$');
+            (col, row) = (left as i64, cell.1 as i64);
+        }
+        push_run(cell.0 as i64 - col, 'b');
+        push_run(1, 'o');
+        col = cell.0 as i64 + 1;
+    }
+
+    let mut contents = String::default();
+    if let Some(name) = &pattern.name {
+        contents.push_str(&format!("#N {}\n", name));
+    }
+    for comment in pattern.comments.iter() {
+        contents.push_str(&format!("#C {}\n", comment));
+    }
+    // Rows go downwards, and wrap around like the cells do.
+    contents.push_str(&format!("#CXRLE Pos={},{}\n", left, top.wrapping_neg()));
+    contents.push_str(&format!(
+        "x = {}, y = {}, rule = {}\n",
+        if cells.is_empty() { 0 } else { right as i64 - left as i64 + 1 },
+        if cells.is_empty() { 0 } else { top as i64 - bottom as i64 + 1 },
+        pattern.rule.unwrap_or_default()
+    ));
+
+    let mut line = String::default();
+    let tokens = runs
+        .iter()
+        .map(|&(count, tag)| match count {
+            1 => tag.to_string(),
+            _ => format!("{}{}", count, tag),
+        })
+        .chain(["!".to_string()]);
+    for token in tokens {
+        if line.len() + token.len() > LINE_WIDTH {
+            contents.push_str(&line);
+            contents.push('\n');
+            line.clear();
+        }
+        line.push_str(&token);
+    }
+    contents.push_str(&line);
+    contents.push('\n');
+
+    contents
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut cells: Vec<Cell>) -> Vec<Cell> {
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn parses_glider() {
+        let pattern = parse("#N Glider\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!").unwrap();
+        assert_eq!(pattern.name.as_deref(), Some("Glider"));
+        assert_eq!(pattern.rule, Some(Rule::CONWAY));
+        assert_eq!(sorted(pattern.cells), vec![(0, -2), (1, -2), (1, 0), (2, -2), (2, -1)]);
+    }
+
+    #[test]
+    fn parses_multi_digit_runs() {
+        let pattern = parse("x = 14, y = 12\no12bo$10$13o!").unwrap();
+        let mut expected = vec![(0, 0), (13, 0)];
+        expected.extend((0..13).map(|x| (x, -11)));
+        assert_eq!(sorted(pattern.cells), sorted(expected));
+    }
+
+    #[test]
+    fn parses_position() {
+        let pattern = parse("#CXRLE Pos=2147483646,-2147483647\nx = 3, y = 1\n3o!").unwrap();
+        assert_eq!(pattern.cells, vec![(2147483646, 2147483647), (2147483647, 2147483647), (i32::MIN, 2147483647)]);
+    }
+
+    #[test]
+    fn rejects_missing_header() {
+        assert!(parse("bo$2bo$3o!").is_err());
+        assert!(parse("#C only a comment").is_err());
+    }
+
+    #[test]
+    fn rejects_runs_beyond_size() {
+        for contents in [
+            "x = 3, y = 1\n99999999999999o!",
+            "x = 3, y = 1\n4o!",
+            "x = 3, y = 1\n2bo2b!",
+            "x = 3, y = 2\n3o2$o!",
+            "x = 3\n3o!",
+            "x = -3, y = 1\n3o!",
+        ] {
+            assert!(parse(contents).is_err(), "{}", contents);
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let cells = vec![(-40, 7), (-40, 6), (0, 0), (25, 0), (26, 0), (3, -120), (1000, -121)];
+        let pattern = Pattern {
+            cells: cells.clone(),
+            rule: Some("B36/S23".parse().unwrap()),
+            name: Some("Scattered".to_string()),
+            comments: vec!["A comment".to_string()],
+        };
+
+        let written = write(&pattern);
+        assert!(written.lines().all(|line| line.len() <= LINE_WIDTH));
+
+        let read = parse(&written).unwrap();
+        assert_eq!(sorted(read.cells), sorted(cells));
+        assert_eq!(read.rule, pattern.rule);
+        assert_eq!(read.name, pattern.name);
+        assert_eq!(read.comments, pattern.comments);
+    }
+
+    #[test]
+    fn round_trips_at_edge_of_universe() {
+        for cells in [
+            vec![(5, i32::MIN), (6, i32::MIN)],
+            vec![(i32::MAX, i32::MAX), (i32::MIN, i32::MIN), (0, 0)],
+        ] {
+            let read = parse(&write(&Pattern { cells: cells.clone(), ..Default::default() })).unwrap();
+            assert_eq!(sorted(read.cells), sorted(cells));
+        }
+    }
+
+    #[test]
+    fn round_trips_empty() {
+        let read = parse(&write(&Pattern::default())).unwrap();
+        assert!(read.cells.is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Outer-totalistic Life-like rule. Bit n of each mask is set when a cell with
+// n living neighbors is born (or survives).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rule {
+    birth: u16,
+    survival: u16,
+}
+
+impl Rule {
+    pub const CONWAY: Rule = Rule { birth: 1 << 3, survival: (1 << 2) | (1 << 3) };
+
+    pub fn born(&self, neighbor_count: u8) -> bool {
+        self.birth & (1 << neighbor_count) != 0
+    }
+
+    pub fn survives(&self, neighbor_count: u8) -> bool {
+        self.survival & (1 << neighbor_count) != 0
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Rule::CONWAY
+    }
+}
+
+fn parse_counts(digits: &str) -> Result<u16, String> {
+    let mut mask = 0;
+    for digit in digits.chars() {
+        match digit.to_digit(10) {
+            Some(count) if count <= 8 => mask |= 1 << count,
+            _ => return Err(format!("'{}' is not a neighbor count between 0 and 8", digit)),
+        }
+    }
+    Ok(mask)
+}
+
+// Parses rulestrings in B/S notation ("B3/S23", "s23/b3"), as well as the
+// older S/B notation ("23/3").
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(rulestring: &str) -> Result<Self, Self::Err> {
+        let rulestring = rulestring.trim();
+        let (first, second) = rulestring
+            .split_once('/')
+            .ok_or(format!("rule '{}' is missing a '/'", rulestring))?;
+
+        let (birth, survival) = match (first.chars().next(), second.chars().next()) {
+            (Some('B' | 'b'), Some('S' | 's')) => (&first[1..], &second[1..]),
+            (Some('S' | 's'), Some('B' | 'b')) => (&second[1..], &first[1..]),
+            (Some('B' | 'b' | 'S' | 's'), _) | (_, Some('B' | 'b' | 'S' | 's')) => {
+                return Err(format!("rule '{}' must contain both a B and an S part", rulestring));
+            }
+            _ => (second, first),
+        };
+
+        let rule = Rule {
+            birth: parse_counts(birth)?,
+            survival: parse_counts(survival)?,
+        };
+
+        // Only neighbors of live cells are ever looked at, so a rule where
+        // cells are born with zero neighbors cannot be simulated.
+        if rule.born(0) {
+            return Err("rules containing B0 are not supported".to_string());
+        }
+
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "B")?;
+        for count in (0..=8).filter(|count| self.born(*count)) {
+            write!(f, "{}", count)?;
+        }
+        write!(f, "/S")?;
+        for count in (0..=8).filter(|count| self.survives(*count)) {
+            write!(f, "{}", count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_notations() {
+        let highlife = Rule { birth: (1 << 3) | (1 << 6), survival: (1 << 2) | (1 << 3) };
+        for rulestring in ["B36/S23", "b36/s23", "S23/B36", " s23/b36 ", "23/36"] {
+            assert_eq!(rulestring.parse::<Rule>(), Ok(highlife), "{}", rulestring);
+        }
+        assert_eq!("B3/S".parse::<Rule>(), Ok(Rule { birth: 1 << 3, survival: 0 }));
+    }
+
+    #[test]
+    fn rejects_b0() {
+        assert!("B03/S23".parse::<Rule>().is_err());
+        assert!("23/03".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        for rulestring in ["B3S23", "B3/23", "B9/S23", "B3/Sx", "B3/B3", ""] {
+            assert!(rulestring.parse::<Rule>().is_err(), "{}", rulestring);
+        }
+    }
+
+    #[test]
+    fn displays_in_b_s_notation() {
+        assert_eq!("23/36".parse::<Rule>().unwrap().to_string(), "B36/S23");
+        assert_eq!(Rule::default().to_string(), "B3/S23");
+    }
+}
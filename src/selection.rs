@@ -0,0 +1,57 @@
+use crate::state::Cell;
+
+// Returns the lower left and upper right corners of the rectangle spanned by
+// the two corners.
+pub fn bounds(corner: Cell, other_corner: Cell) -> (Cell, Cell) {
+    (
+        (corner.0.min(other_corner.0), corner.1.min(other_corner.1)),
+        (corner.0.max(other_corner.0), corner.1.max(other_corner.1)),
+    )
+}
+
+// Moves the cells such that their upper left corner is at (0, 0).
+pub fn normalize(mut cells: Vec<Cell>) -> Vec<Cell> {
+    let left = cells.iter().map(|cell| cell.0).min().unwrap_or(0);
+    let top = cells.iter().map(|cell| cell.1).max().unwrap_or(0);
+
+    for cell in cells.iter_mut() {
+        *cell = (cell.0 - left, cell.1 - top);
+    }
+
+    cells
+}
+
+pub fn translate(mut cells: Vec<Cell>, offset: Cell) -> Vec<Cell> {
+    for cell in cells.iter_mut() {
+        *cell = (cell.0 + offset.0, cell.1 + offset.1);
+    }
+
+    cells
+}
+
+// Rotates the cells 90 degrees clockwise.
+pub fn rotate(mut cells: Vec<Cell>) -> Vec<Cell> {
+    for cell in cells.iter_mut() {
+        *cell = (cell.1, -cell.0);
+    }
+
+    normalize(cells)
+}
+
+// Mirrors the cells left to right.
+pub fn flip_horizontally(mut cells: Vec<Cell>) -> Vec<Cell> {
+    for cell in cells.iter_mut() {
+        cell.0 = -cell.0;
+    }
+
+    normalize(cells)
+}
+
+// Mirrors the cells top to bottom.
+pub fn flip_vertically(mut cells: Vec<Cell>) -> Vec<Cell> {
+    for cell in cells.iter_mut() {
+        cell.1 = -cell.1;
+    }
+
+    normalize(cells)
+}
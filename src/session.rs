@@ -0,0 +1,267 @@
+use std::fs;
+use crate::rle::{self, Pattern};
+use crate::rule::Rule;
+use crate::state::{Cell, State};
+use crate::topology::Topology;
+
+const HEADER: &str = "#gol session";
+const CELLS_HEADER: &str = "#cells";
+
+// Amount of bookmarks, bound to the number keys 1 to 9 in the GUI.
+pub const BOOKMARK_AMOUNT: usize = 9;
+
+// Everything needed to pick up where a run of gol left off. Written as a text
+// file with a setting per line, followed by the cells in RLE:
+//
+//     #gol session
+//     generation 120
+//     rule B3/S23
+//     topology torus:100x80
+//     view -12.5 3
+//     scale 10
+//     dark-mode true
+//     bookmark 1 40 -7.5
+//     #cells
+//     #CXRLE Pos=0,-2
+//     x = 3, y = 3, rule = B3/S23
+//     bo$2bo$3o!
+pub struct Session {
+    pub cells: Vec<Cell>,
+    pub generation: usize,
+    pub rule: Rule,
+    pub topology: Topology,
+    pub view: (f64, f64),
+    pub scale: f64,
+    pub dark_mode: bool,
+    // Views jumped to with the number keys in the GUI.
+    pub bookmarks: [Option<(f64, f64)>; BOOKMARK_AMOUNT],
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            cells: Vec::new(),
+            generation: 0,
+            rule: Rule::default(),
+            topology: Topology::default(),
+            view: (0.0, 0.0),
+            scale: 10.0,
+            dark_mode: true,
+            bookmarks: [None; BOOKMARK_AMOUNT],
+        }
+    }
+}
+
+impl Session {
+    // Takes the cells, generation, rule and topology from the state.
+    pub fn capture(&mut self, state: &dyn State) {
+        self.cells = state.collect_cells();
+        self.generation = state.generation();
+        self.rule = state.rule();
+        self.topology = state.topology();
+    }
+
+    // Replaces the cells, generation, rule and topology of the state. The state
+    // is left as it was if its engine cannot simulate the topology.
+    pub fn restore(&self, state: &mut dyn State) -> Result<(), String> {
+        state.set_topology(self.topology)?;
+        let cells = state.collect_cells();
+        state.remove_cells(cells);
+        state.set_rule(self.rule);
+        state.insert_cells(self.cells.clone());
+        state.set_generation(self.generation);
+
+        Ok(())
+    }
+}
+
+pub fn write(path: &str, session: &Session) -> Result<(), String> {
+    let mut contents = format!("{}\n", HEADER);
+    contents.push_str(&format!("generation {}\n", session.generation));
+    contents.push_str(&format!("rule {}\n", session.rule));
+    contents.push_str(&format!("topology {}\n", session.topology));
+    contents.push_str(&format!("view {} {}\n", session.view.0, session.view.1));
+    contents.push_str(&format!("scale {}\n", session.scale));
+    contents.push_str(&format!("dark-mode {}\n", session.dark_mode));
+    for (index, bookmark) in session.bookmarks.iter().enumerate() {
+        if let Some(view) = bookmark {
+            contents.push_str(&format!("bookmark {} {} {}\n", index + 1, view.0, view.1));
+        }
+    }
+
+    contents.push_str(&format!("{}\n", CELLS_HEADER));
+    contents.push_str(&rle::write(&Pattern {
+        cells: session.cells.clone(),
+        rule: Some(session.rule),
+        ..Default::default()
+    }));
+
+    fs::write(path, contents).map_err(|error| format!("could not save session '{}': {}", path, error))
+}
+
+pub fn read(path: &str) -> Result<Session, String> {
+    let contents = fs::read_to_string(path).map_err(|error| format!("could not read session '{}': {}", path, error))?;
+    parse(&contents).map_err(|error| format!("could not read session '{}': {}", path, error))
+}
+
+pub fn parse(contents: &str) -> Result<Session, String> {
+    let mut lines = contents.lines();
+    if lines.next().map(str::trim) != Some(HEADER) {
+        return Err(format!("it should start with '{}'", HEADER));
+    }
+
+    let mut session = Session::default();
+    for line in lines.by_ref() {
+        let line = line.trim();
+        if line == CELLS_HEADER {
+            break;
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let value = value.trim();
+        let error = || format!("invalid {} '{}'", key, value);
+        match key {
+            "generation" => session.generation = value.parse().map_err(|_| error())?,
+            "rule" => session.rule = value.parse()?,
+            "topology" => session.topology = value.parse()?,
+            "view" => session.view = parse_view(value).ok_or_else(error)?,
+            // Zooming in the GUI keeps the scale between 1 and 30.
+            "scale" => {
+                session.scale = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|scale| *scale > 1.0 && *scale < 30.0)
+                    .ok_or_else(error)?
+            }
+            "dark-mode" => session.dark_mode = value.parse().map_err(|_| error())?,
+            "bookmark" => {
+                let (number, view) = value.split_once(' ').ok_or_else(error)?;
+                let index = number
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|number| (1..=BOOKMARK_AMOUNT).contains(number))
+                    .ok_or_else(error)?
+                    - 1;
+                session.bookmarks[index] = Some(parse_view(view).ok_or_else(error)?);
+            }
+            _ => return Err(format!("unknown setting '{}'", key)),
+        }
+    }
+
+    let cells: Vec<&str> = lines.collect();
+    session.cells = rle::parse(&cells.join("\n"))?.cells;
+
+    Ok(session)
+}
+
+fn parse_view(text: &str) -> Option<(f64, f64)> {
+    let mut numbers = text.split_whitespace().map(|number| number.parse::<f64>().ok().filter(|n| n.is_finite()));
+    match (numbers.next(), numbers.next(), numbers.next()) {
+        (Some(Some(x)), Some(Some(y)), None) => Some((x, y)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "#gol session
+generation 120
+rule B36/S23
+topology torus:100x80
+view -12.5 3
+scale 10
+dark-mode false
+bookmark 1 40 -7.5
+bookmark 9 0 0
+#cells
+#CXRLE Pos=0,-2
+x = 3, y = 3, rule = B36/S23
+bo$2bo$3o!
+";
+
+    #[test]
+    fn parses_every_setting() {
+        let session = parse(EXAMPLE).unwrap();
+        let mut cells = session.cells.clone();
+        cells.sort_unstable();
+
+        assert_eq!(session.generation, 120);
+        assert_eq!(session.rule, "B36/S23".parse().unwrap());
+        assert!(session.topology == Topology::Torus { width: 100, height: 80 });
+        assert_eq!(session.view, (-12.5, 3.0));
+        assert_eq!(session.scale, 10.0);
+        assert!(!session.dark_mode);
+        assert_eq!(session.bookmarks[0], Some((40.0, -7.5)));
+        assert_eq!(session.bookmarks[8], Some((0.0, 0.0)));
+        assert!(session.bookmarks[1..8].iter().all(Option::is_none));
+        assert_eq!(cells, vec![(0, 0), (1, 0), (1, 2), (2, 0), (2, 1)]);
+    }
+
+    #[test]
+    fn leaves_missing_settings_default() {
+        let session = parse("#gol session\n#cells\nx = 0, y = 0\n!\n").unwrap();
+        let default = Session::default();
+
+        assert!(session.cells.is_empty());
+        assert_eq!(session.generation, default.generation);
+        assert_eq!(session.rule, default.rule);
+        assert_eq!(session.view, default.view);
+        assert_eq!(session.scale, default.scale);
+        assert_eq!(session.dark_mode, default.dark_mode);
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        for line in [
+            "generation -1",
+            "rule B0/S23",
+            "topology torus",
+            "view 1",
+            "view 1 NaN",
+            "scale 0",
+            "scale -3",
+            "scale NaN",
+            "scale 1e9",
+            "dark-mode maybe",
+            "bookmark 0 1 1",
+            "bookmark 10 1 1",
+            "bookmark 1 inf 1",
+            "zoom 10",
+        ] {
+            let contents = format!("#gol session\n{}\n#cells\nx = 0, y = 0\n!\n", line);
+            assert!(parse(&contents).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn rejects_missing_header() {
+        assert!(parse("generation 3\n#cells\nx = 0, y = 0\n!\n").is_err());
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut session = parse(EXAMPLE).unwrap();
+        session.cells.sort_unstable();
+
+        let path = std::env::temp_dir().join(format!("gol-session-test-{}.session", std::process::id()));
+        let path = path.to_str().unwrap();
+        write(path, &session).unwrap();
+        let mut read = read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        read.cells.sort_unstable();
+
+        assert_eq!(read.cells, session.cells);
+        assert_eq!(read.generation, session.generation);
+        assert_eq!(read.rule, session.rule);
+        assert!(read.topology == session.topology);
+        assert_eq!(read.view, session.view);
+        assert_eq!(read.scale, session.scale);
+        assert_eq!(read.dark_mode, session.dark_mode);
+        assert_eq!(read.bookmarks, session.bookmarks);
+    }
+}
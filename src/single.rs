@@ -1,7 +1,13 @@
 use fxhash::FxHashSet as HashSet;
+use crate::activity::{self, Activity};
+use crate::ages::{self, CellAges};
 use crate::state::*;
+use crate::rule::Rule;
+use crate::topology::Topology;
+#[cfg(feature = "gui")]
 use nannou::prelude::geom::Tri;
-use nannou::rand::random_range;
+use rand::Rng;
+#[cfg(feature = "gui")]
 use std::collections::LinkedList;
 
 pub struct SingleState {
@@ -9,6 +15,10 @@ pub struct SingleState {
     kill_list: Vec<Cell>,
     res_list: Vec<Cell>,
     generation: usize,
+    rule: Rule,
+    topology: Topology,
+    ages: Option<CellAges>,
+    activity: Option<Activity>,
 }
 
 pub fn single_state() -> SingleState {
@@ -19,11 +29,19 @@ pub fn single_state() -> SingleState {
 
     let generation: usize = 0;
 
+    let rule = Rule::default();
+
+    let topology = Topology::default();
+
     SingleState {
         cells,
         kill_list,
         res_list,
         generation,
+        rule,
+        topology,
+        ages: None,
+        activity: None,
     }
 }
 
@@ -33,74 +51,191 @@ impl State for SingleState {
             self.generation += 1;
         }
 
+        // The lists are kept around after ticking, as the changes of the tick.
+        self.kill_list.clear();
+        self.res_list.clear();
+
+        let topology = self.topology;
         for cell in self.cells.iter() {
             // Mark cell for death by neighbor amount.
-            let neighbors: [Cell; 8] = get_neighbors(&cell);
+            let neighbors: [Cell; 8] = get_neighbors(cell, topology);
             let neighbor_count = count_living_neighbors(&neighbors, &self.cells);
-            if neighbor_count < 2 || neighbor_count > 3 {
+            if !self.rule.survives(neighbor_count) {
                 self.kill_list.push(*cell);
             }
 
             // Iterate through dead neighbors, mark ones deserving for life.
             for neighbor in neighbors
                 .iter()
-                .filter(|&&neighbor| !self.cells.contains(&neighbor))
+                .filter(|&&neighbor| !self.cells.contains(&neighbor) && topology.contains(&neighbor))
             {
-                let neighbor_neighbors: [Cell; 8] = get_neighbors(&neighbor);
+                let neighbor_neighbors: [Cell; 8] = get_neighbors(neighbor, topology);
                 let neighbor_count = count_living_neighbors(&neighbor_neighbors, &self.cells);
-                if neighbor_count == 3 {
+                if self.rule.born(neighbor_count) {
                     self.res_list.push(*neighbor);
                 }
             }
         }
 
-        for cell in self.kill_list.drain(0..) {
-            self.cells.remove(&cell);
+        for cell in self.kill_list.iter() {
+            self.cells.remove(cell);
         }
-        for resurrected_cell in self.res_list.drain(0..) {
-            self.cells.insert(resurrected_cell);
+        // Cells with several live neighbors are marked more than once.
+        let cells = &mut self.cells;
+        self.res_list.retain(|resurrected_cell| cells.insert(*resurrected_cell));
+
+        if let Some(ages) = self.ages.as_mut() {
+            ages.update(self.generation, &self.res_list, &self.kill_list);
+        }
+        if let Some(activity) = self.activity.as_mut() {
+            activity.update(&self.res_list, &self.kill_list);
         }
     }
 
+    fn last_changes(&self) -> Option<Changes<'_>> {
+        Some(Changes {
+            births: &self.res_list,
+            deaths: &self.kill_list,
+        })
+    }
+
     fn insert_cells(&mut self, mut collection: Vec<Cell>) {
         for cell in collection.drain(0..) {
-            self.cells.insert(cell);
+            self.insert_cell(cell);
         }
     }
 
     fn insert_cells_rel(&mut self, mut collection: Vec<Cell>, view: (f64, f64)) {
         for cell in collection.drain(0..) {
-            self.cells.insert((cell.0 + view.1.floor() as i32, cell.1 - view.0.floor() as i32));
+            self.insert_cell(relative_to_view(cell, view));
         }
     }
     
     fn insert_cell(&mut self, cell: Cell) {
-        self.cells.insert(cell);
+        if let Some(cell) = self.topology.wrap(cell) {
+            self.cells.insert(cell);
+            if let Some(ages) = self.ages.as_mut() {
+                ages.insert(self.generation, cell);
+            }
+        }
+    }
+
+    fn remove_cell(&mut self, cell: Cell) {
+        if let Some(cell) = self.topology.wrap(cell) {
+            self.cells.remove(&cell);
+            if let Some(ages) = self.ages.as_mut() {
+                ages.remove(&cell);
+            }
+        }
+    }
+
+    fn toggle_cell(&mut self, cell: Cell) {
+        if let Some(cell) = self.topology.wrap(cell) {
+            match self.cells.contains(&cell) {
+                true => self.remove_cell(cell),
+                _ => self.insert_cell(cell),
+            }
+        }
+    }
+
+    fn is_alive(&self, cell: Cell) -> bool {
+        self.topology.wrap(cell).is_some_and(|cell| self.cells.contains(&cell))
+    }
+
+    fn remove_cells(&mut self, mut collection: Vec<Cell>) {
+        for cell in collection.drain(0..) {
+            self.remove_cell(cell);
+        }
     }
     
     fn collect_cells(&self) -> Vec<Cell> {
         let mut collection = Vec::default();
 
         for cell in self.cells.iter() {
-            collection.push(cell.clone());
+            collection.push(*cell);
         }
 
         collection
     }
 
+    fn cells_in_rect(&self, min: Cell, max: Cell) -> Vec<Cell> {
+        self.cells
+            .iter()
+            .filter(|cell| in_rect(cell, min, max))
+            .copied()
+            .collect()
+    }
+
     fn count_cells(&self) -> usize {
         self.cells.len()
     }
 
     fn random_cell(&self) -> Cell {
-        let random_index = random_range(0, self.cells.len());
+        let random_index = rand::thread_rng().gen_range(0..self.cells.len());
         *(self.cells.iter().nth(random_index).unwrap())
     }
     
     fn generation(&self) -> usize {
         self.generation
     }
+
+    fn set_generation(&mut self, generation: usize) {
+        self.generation = generation;
+    }
+
+    fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+    }
+
+    fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    fn set_topology(&mut self, topology: Topology) -> Result<(), String> {
+        let collection = self.collect_cells();
+        self.topology = topology;
+        self.cells.clear();
+        if let Some(ages) = self.ages.as_mut() {
+            ages.clear();
+        }
+        if let Some(activity) = self.activity.as_mut() {
+            activity.clear();
+        }
+        self.insert_cells(collection);
+
+        Ok(())
+    }
+
+    fn set_age_tracking(&mut self, enabled: bool) {
+        self.ages = match enabled {
+            true => {
+                let mut ages = ages::cell_ages();
+                for cell in self.cells.iter() {
+                    ages.insert(self.generation, *cell);
+                }
+                Some(ages)
+            }
+            _ => None,
+        };
+    }
+
+    fn ages(&self) -> Option<&CellAges> {
+        self.ages.as_ref()
+    }
+
+    fn set_activity_tracking(&mut self, enabled: bool) {
+        self.activity = enabled.then(activity::activity);
+    }
+
+    fn activity(&self) -> Option<&Activity> {
+        self.activity.as_ref()
+    }
     
+    #[cfg(feature = "gui")]
     fn get_tris(
         &self, 
         view: (f64, f64), 
@@ -113,19 +248,7 @@ impl State for SingleState {
         let mut tri_list = LinkedList::default();
         
         for cell in self.cells.iter().filter(|cell| cell.0 > screen_left && cell.0 < screen_right && cell.1 > screen_bottom && cell.1 < screen_top) {
-            let point = [(cell.0 as f64 + view.0 - 0.5) as f32, (cell.1 as f64 + view.1 - 0.5) as f32];
-
-            let first_tri = nannou::prelude::geom::Tri([
-                ([point[0], point[1], 0.0], cell_color),
-                ([point[0] + 1.0, point[1], 0.0], cell_color),
-                ([point[0] + 1.0, point[1] + 1.0, 0.0], cell_color)
-            ]);
-
-            let second_tri = nannou::prelude::geom::Tri([
-                first_tri[0], 
-                ([point[0], point[1] + 1.0, 0.0], cell_color), 
-                first_tri[2]
-            ]);
+            let [first_tri, second_tri] = cell_tris(cell, view, cell_color);
             
             tri_list.push_front(first_tri);
             tri_list.push_front(second_tri);
@@ -0,0 +1,54 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::state::Cell;
+
+// A rectangle randomly filled with cells, the same way every time for the
+// same seed.
+#[derive(Clone, Copy)]
+pub struct Soup {
+    pub width: u32,
+    pub height: u32,
+    pub density: f64,
+    pub seed: u64,
+}
+
+impl Soup {
+    // Returns the cells of the soup, centered on the given cell.
+    pub fn cells(&self, center: Cell) -> Vec<Cell> {
+        let mut r = StdRng::seed_from_u64(self.seed);
+        let (left, bottom) = (
+            center.0.wrapping_sub((self.width / 2) as i32),
+            center.1.wrapping_sub((self.height / 2) as i32)
+        );
+
+        let mut collection = Vec::default();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if r.gen_bool(self.density) {
+                    collection.push((left.wrapping_add(x as i32), bottom.wrapping_add(y as i32)));
+                }
+            }
+        }
+
+        collection
+    }
+}
+
+// Parses sizes written as "WIDTHxHEIGHT".
+pub fn parse_size(size: &str) -> Result<(u32, u32), String> {
+    let (width, height) = size
+        .split_once(['x', 'X'])
+        .ok_or(format!("size '{}' should be written as WIDTHxHEIGHT", size))?;
+
+    match (width.trim().parse(), height.trim().parse()) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(format!("size '{}' should be written as WIDTHxHEIGHT", size)),
+    }
+}
+
+pub fn parse_density(density: &str) -> Result<f64, String> {
+    match density.trim().parse::<f64>() {
+        Ok(density) if (0.0..=1.0).contains(&density) => Ok(density),
+        _ => Err(format!("density '{}' should be a number between 0 and 1", density)),
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+use std::time::Duration;
+
+// Speeds stepped through when speeding up or slowing down, before and after
+// going unthrottled.
+const TICKS_PER_SECOND: [u32; 9] = [1, 2, 5, 10, 20, 40, 60, 120, 240];
+
+// Most generations advanced by a single tick are 2^MAX_STEP_EXPONENT.
+pub const MAX_STEP_EXPONENT: u32 = 30;
+
+// Most generations advanced by a single tick of engines that step one
+// generation at a time, which cannot be cut short once started.
+pub const TICKED_STEP_EXPONENT: u32 = 4;
+
+// How fast the cells are advanced in the GUI. Each tick advances the cells by
+// 2^step_exponent generations.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Speed {
+    // None ticks as often as possible.
+    pub ticks_per_second: Option<u32>,
+    pub step_exponent: u32,
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed {
+            ticks_per_second: Some(40),
+            step_exponent: 0,
+        }
+    }
+}
+
+impl Speed {
+    // Time between ticks, or None if unthrottled.
+    pub fn interval(&self) -> Option<Duration> {
+        self.ticks_per_second
+            .map(|ticks_per_second| Duration::from_secs(1) / ticks_per_second)
+    }
+
+    pub fn faster(&mut self) {
+        if let Some(current) = self.ticks_per_second {
+            self.ticks_per_second = TICKS_PER_SECOND
+                .iter()
+                .copied()
+                .find(|&ticks_per_second| ticks_per_second > current);
+        }
+    }
+
+    pub fn slower(&mut self) {
+        self.ticks_per_second = match self.ticks_per_second {
+            None => TICKS_PER_SECOND.last().copied(),
+            Some(current) => Some(
+                TICKS_PER_SECOND
+                    .iter()
+                    .copied()
+                    .rev()
+                    .find(|&ticks_per_second| ticks_per_second < current)
+                    .unwrap_or(current)
+            ),
+        };
+    }
+
+    // Grows the step size, up to 2^limit generations.
+    pub fn bigger_steps(&mut self, limit: u32) {
+        self.step_exponent = (self.step_exponent + 1).min(limit.min(MAX_STEP_EXPONENT));
+    }
+
+    pub fn smaller_steps(&mut self) {
+        self.step_exponent = self.step_exponent.saturating_sub(1);
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.ticks_per_second {
+            Some(ticks_per_second) => write!(f, "{} ticks/s", ticks_per_second)?,
+            None => write!(f, "Unthrottled")?,
+        }
+        match self.step_exponent {
+            0 => write!(f, ", 1 generation each"),
+            step_exponent => write!(f, ", 2^{} generations each", step_exponent),
+        }
+    }
+}
+
+// Parses ticks per second, or "max" for unthrottled.
+pub fn parse_ticks_per_second(ticks_per_second: &str) -> Result<Option<u32>, String> {
+    let ticks_per_second = ticks_per_second.trim();
+    if ticks_per_second.eq_ignore_ascii_case("max") {
+        return Ok(None);
+    }
+
+    match ticks_per_second.parse::<u32>() {
+        Ok(ticks_per_second) if ticks_per_second > 0 => Ok(Some(ticks_per_second)),
+        _ => Err(format!("speed '{}' should be a positive amount of ticks per second, or 'max'", ticks_per_second)),
+    }
+}
@@ -1,21 +1,79 @@
 use fxhash::FxHashSet as HashSet;
-use std::thread;
-use crate::parallel;
-use crate::single;
+use crate::activity::Activity;
+use crate::ages::CellAges;
+use crate::engine::{self, Engine};
+use crate::rule::Rule;
+use crate::speed;
+use crate::topology::Topology;
+#[cfg(feature = "gui")]
 use nannou::prelude::geom::Tri;
+#[cfg(feature = "gui")]
 use std::collections::LinkedList;
 
 pub type Cell = (i32, i32);
 
+// Cells given life and cells killed by the last tick.
+pub struct Changes<'a> {
+    pub births: &'a [Cell],
+    pub deaths: &'a [Cell],
+}
+
 pub trait State {
     fn tick(&mut self);
+    // Advances the cells by 2^exponent generations.
+    fn step(&mut self, exponent: u32) {
+        for _ in 0..1usize << exponent {
+            self.tick();
+        }
+    }
+    // Largest exponent worth stepping by. Engines that step one generation at
+    // a time keep it small.
+    fn max_step_exponent(&self) -> u32 {
+        speed::TICKED_STEP_EXPONENT
+    }
     fn insert_cells(&mut self, cells: Vec<Cell>);
     fn insert_cells_rel(&mut self, cells: Vec<Cell>, view: (f64, f64));
     fn insert_cell(&mut self, cell: Cell);
+    fn remove_cell(&mut self, cell: Cell);
+    // Kills the cell if it is alive, and gives it life otherwise.
+    fn toggle_cell(&mut self, cell: Cell);
+    fn is_alive(&self, cell: Cell) -> bool;
+    fn remove_cells(&mut self, cells: Vec<Cell>);
     fn collect_cells(&self) -> Vec<Cell>;
+    // Collects the cells within the rectangle, corners included.
+    fn cells_in_rect(&self, min: Cell, max: Cell) -> Vec<Cell>;
+    fn remove_cells_in_rect(&mut self, min: Cell, max: Cell) -> Vec<Cell> {
+        let collection = self.cells_in_rect(min, max);
+        self.remove_cells(collection.clone());
+        collection
+    }
     fn count_cells(&self) -> usize;
     fn random_cell(&self) -> Cell;
     fn generation(&self) -> usize;
+    fn set_generation(&mut self, generation: usize);
+    // Engines that do not know the changes of each tick return None.
+    fn last_changes(&self) -> Option<Changes<'_>> {
+        None
+    }
+    fn rule(&self) -> Rule;
+    fn set_rule(&mut self, rule: Rule);
+    // Starts or stops keeping track of the ages of the cells, starting with
+    // every live cell newborn. Engines that cannot keep track leave it be.
+    fn set_age_tracking(&mut self, _enabled: bool) {}
+    fn ages(&self) -> Option<&CellAges> {
+        None
+    }
+    // Starts counting how many times each cell changes from one tick to the
+    // next, from zero. Engines that cannot count leave it be.
+    fn set_activity_tracking(&mut self, _enabled: bool) {}
+    fn activity(&self) -> Option<&Activity> {
+        None
+    }
+    fn topology(&self) -> Topology;
+    // Changes the shape of the universe, wrapping the cells into it. Engines
+    // that cannot simulate the shape leave the cells be and return an error.
+    fn set_topology(&mut self, topology: Topology) -> Result<(), String>;
+    #[cfg(feature = "gui")]
     fn get_tris(
         &self, 
         view: (f64, f64), 
@@ -28,19 +86,30 @@ pub trait State {
 }
 
 pub fn state() -> Box<dyn State> {
-    match thread::available_parallelism() {
-        Ok(_) => Box::new(parallel::parallel_state()),
-        Err(_) => Box::new(single::single_state()),
-    }
+    let thread_amount = engine::thread_amount().unwrap_or(1);
+    Engine::auto(thread_amount).build(thread_amount)
+}
+
+// Moves a cell of a pattern dropped onto the view to where it lands in the
+// universe.
+pub fn relative_to_view(cell: Cell, view: (f64, f64)) -> Cell {
+    (cell.0 + view.1.floor() as i32, cell.1 - view.0.floor() as i32)
+}
+
+pub fn in_rect(cell: &Cell, min: Cell, max: Cell) -> bool {
+    cell.0 >= min.0 && cell.0 <= max.0 && cell.1 >= min.1 && cell.1 <= max.1
 }
 
 // Returns arrays of the coordinates of the neighbors of the cells coordinates given.
-pub fn get_neighbors(coordinates: &Cell) -> [Cell; 8] {
+// Neighbors beyond the edge of a plane are left outside of it, where they are
+// never alive. A cell is never its own neighbor, which could otherwise happen
+// in the corners of a cross-surface.
+pub fn get_neighbors(coordinates: &Cell, topology: Topology) -> [Cell; 8] {
     let (x, y) = *coordinates;
-    let (x_left, x_right) = (x - 1, x + 1);
-    let (y_up, y_down) = (y - 1, y + 1);
+    let (x_left, x_right) = (x.wrapping_sub(1), x.wrapping_add(1));
+    let (y_up, y_down) = (y.wrapping_sub(1), y.wrapping_add(1));
 
-    [
+    let neighbors = [
         (x_left, y_up),
         (x, y_up),
         (x_right, y_up),
@@ -49,7 +118,41 @@ pub fn get_neighbors(coordinates: &Cell) -> [Cell; 8] {
         (x_left, y_down),
         (x, y_down),
         (x_right, y_down),
-    ]
+    ];
+
+    match topology {
+        Topology::Unbounded | Topology::Plane { .. } => neighbors,
+        _ => neighbors.map(|neighbor| {
+            topology
+                .wrap(neighbor)
+                .filter(|wrapped| wrapped != coordinates)
+                .unwrap_or(neighbor)
+        }),
+    }
+}
+
+// Returns the two triangles covering the cell on screen.
+#[cfg(feature = "gui")]
+pub fn cell_tris(
+    cell: &Cell,
+    view: (f64, f64),
+    cell_color: nannou::prelude::rgb::Rgb
+) -> [Tri<([f32; 3], nannou::prelude::rgb::Rgb)>; 2] {
+    let point = [(cell.0 as f64 + view.0 - 0.5) as f32, (cell.1 as f64 + view.1 - 0.5) as f32];
+
+    let first_tri = nannou::prelude::geom::Tri([
+        ([point[0], point[1], 0.0], cell_color),
+        ([point[0] + 1.0, point[1], 0.0], cell_color),
+        ([point[0] + 1.0, point[1] + 1.0, 0.0], cell_color)
+    ]);
+
+    let second_tri = nannou::prelude::geom::Tri([
+        first_tri[0],
+        ([point[0], point[1] + 1.0, 0.0], cell_color),
+        first_tri[2]
+    ]);
+
+    [first_tri, second_tri]
 }
 
 pub fn count_living_neighbors(neighbors: &[Cell; 8], cells: &HashSet<Cell>) -> u8 {
@@ -0,0 +1,96 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use crate::state::{Cell, State};
+
+const CSV_HEADER: &str = "generation,population,births,deaths,min_x,min_y,max_x,max_y";
+
+// Writes the generation, population, births, deaths and bounding box of the
+// cells to a file, one line at a time. Files ending with ".json", ".jsonl" or
+// ".ndjson" get a JSON object per line, other files get CSV.
+pub struct StatsWriter {
+    path: String,
+    writer: BufWriter<File>,
+    json: bool,
+}
+
+pub fn stats_writer(path: String) -> Result<StatsWriter, String> {
+    let file = File::create(&path).map_err(|error| format!("could not create '{}': {}", path, error))?;
+    let lowercase_path = path.to_lowercase();
+    let json = [".json", ".jsonl", ".ndjson"]
+        .iter()
+        .any(|extension| lowercase_path.ends_with(extension));
+
+    let mut stats_writer = StatsWriter {
+        path,
+        writer: BufWriter::new(file),
+        json,
+    };
+    if !stats_writer.json {
+        stats_writer.write_line(CSV_HEADER.to_string())?;
+    }
+
+    Ok(stats_writer)
+}
+
+fn bounding_box(cells: &[Cell]) -> Option<(Cell, Cell)> {
+    let min_x = cells.iter().map(|cell| cell.0).min()?;
+    let min_y = cells.iter().map(|cell| cell.1).min()?;
+    let max_x = cells.iter().map(|cell| cell.0).max()?;
+    let max_y = cells.iter().map(|cell| cell.1).max()?;
+
+    Some(((min_x, min_y), (max_x, max_y)))
+}
+
+impl StatsWriter {
+    // Writes the current generation of the cells. Births and deaths are left
+    // out unless asked for, and if the engine does not know them.
+    pub fn write(&mut self, state: &dyn State, with_changes: bool) -> Result<(), String> {
+        let changes = match with_changes {
+            true => state.last_changes().map(|changes| (changes.births.len(), changes.deaths.len())),
+            _ => None,
+        };
+        let bounds = bounding_box(&state.collect_cells());
+
+        let line = match self.json {
+            true => {
+                let (births, deaths) = match changes {
+                    Some((births, deaths)) => (births.to_string(), deaths.to_string()),
+                    None => ("null".to_string(), "null".to_string()),
+                };
+                let bounds = match bounds {
+                    Some((min, max)) => format!("{{\"min\":[{},{}],\"max\":[{},{}]}}", min.0, min.1, max.0, max.1),
+                    None => "null".to_string(),
+                };
+                format!(
+                    "{{\"generation\":{},\"population\":{},\"births\":{},\"deaths\":{},\"bounds\":{}}}",
+                    state.generation(),
+                    state.count_cells(),
+                    births,
+                    deaths,
+                    bounds,
+                )
+            }
+            _ => {
+                let (births, deaths) = match changes {
+                    Some((births, deaths)) => (births.to_string(), deaths.to_string()),
+                    None => (String::new(), String::new()),
+                };
+                let bounds = match bounds {
+                    Some((min, max)) => format!("{},{},{},{}", min.0, min.1, max.0, max.1),
+                    None => ",,,".to_string(),
+                };
+                format!("{},{},{},{},{}", state.generation(), state.count_cells(), births, deaths, bounds)
+            }
+        };
+
+        self.write_line(line)
+    }
+
+    fn write_line(&mut self, line: String) -> Result<(), String> {
+        writeln!(self.writer, "{}", line).map_err(|error| format!("could not write '{}': {}", self.path, error))
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|error| format!("could not write '{}': {}", self.path, error))
+    }
+}
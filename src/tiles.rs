@@ -0,0 +1,436 @@
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use crate::activity::{self, Activity};
+use crate::ages::{self, CellAges};
+use crate::state::*;
+use crate::rule::Rule;
+use crate::topology::Topology;
+#[cfg(feature = "gui")]
+use nannou::prelude::geom::Tri;
+use rand::Rng;
+#[cfg(feature = "gui")]
+use std::collections::LinkedList;
+
+// Width and height of each tile, in cells. A row of a tile fits in a u64.
+const TILE_SIZE: usize = 64;
+const TILE_SHIFT: u32 = 6;
+
+// Bit x of row y is the cell at (x, y) within the tile.
+type Tile = [u64; TILE_SIZE];
+
+const EMPTY_TILE: Tile = [0; TILE_SIZE];
+
+// The universe split into 64 by 64 tiles, stored as bits, of which only the
+// ones with live cells are kept. Neighbors are counted for a whole row of a
+// tile at once, with bitwise operations.
+pub struct TileState {
+    tiles: HashMap<Cell, Tile>,
+    births: Vec<Cell>,
+    deaths: Vec<Cell>,
+    generation: usize,
+    rule: Rule,
+    ages: Option<CellAges>,
+    activity: Option<Activity>,
+}
+
+pub fn tile_state() -> TileState {
+    TileState {
+        tiles: HashMap::default(),
+        births: Vec::new(),
+        deaths: Vec::new(),
+        generation: 0,
+        rule: Rule::default(),
+        ages: None,
+        activity: None,
+    }
+}
+
+// Tile coordinates go around at the edges, like cell coordinates do.
+fn wrap_tile(coordinate: i32) -> i32 {
+    coordinate.wrapping_shl(TILE_SHIFT) >> TILE_SHIFT
+}
+
+fn tile_of(cell: Cell) -> (Cell, usize, usize) {
+    (
+        (cell.0 >> TILE_SHIFT, cell.1 >> TILE_SHIFT),
+        (cell.0 & (TILE_SIZE as i32 - 1)) as usize,
+        (cell.1 & (TILE_SIZE as i32 - 1)) as usize,
+    )
+}
+
+fn tile_origin(key: Cell) -> Cell {
+    (key.0 << TILE_SHIFT, key.1 << TILE_SHIFT)
+}
+
+// Adds three words bit by bit, returning the sum and carry bits.
+fn full_add(a: u64, b: u64, c: u64) -> (u64, u64) {
+    let partial = a ^ b;
+    (partial ^ c, (a & b) | (c & partial))
+}
+
+// Counts the live neighbors of each bit out of eight words, returning the
+// count as four words of bits, lowest first.
+fn count_bits(words: [u64; 8]) -> [u64; 4] {
+    let (first_sum, first_carry) = full_add(words[0], words[1], words[2]);
+    let (second_sum, second_carry) = full_add(words[3], words[4], words[5]);
+    let (third_sum, third_carry) = (words[6] ^ words[7], words[6] & words[7]);
+
+    let (ones, ones_carry) = full_add(first_sum, second_sum, third_sum);
+    let (twos, twos_carry) = full_add(first_carry, second_carry, third_carry);
+    let (twos, fours_carry) = (twos ^ ones_carry, twos & ones_carry);
+
+    [ones, twos, twos_carry ^ fours_carry, twos_carry & fours_carry]
+}
+
+// Bits of the cells with exactly the amount of neighbors.
+fn count_is(count: [u64; 4], amount: u8) -> u64 {
+    (0..4).fold(u64::MAX, |mask, bit| match amount & (1 << bit) {
+        0 => mask & !count[bit],
+        _ => mask & count[bit],
+    })
+}
+
+// The tiles around a tile, in rows from south to north, west to east.
+struct Neighborhood<'a> {
+    tiles: [[&'a Tile; 3]; 3],
+}
+
+impl Neighborhood<'_> {
+    // Returns the row of cells, with each bit holding the cell to the west,
+    // the cell itself and the cell to the east. Rows -1 and 64 come from the
+    // tiles to the south and north.
+    fn row(&self, row: isize) -> (u64, u64, u64) {
+        let (tile_row, row) = match row {
+            -1 => (0, TILE_SIZE - 1),
+            row if row == TILE_SIZE as isize => (2, 0),
+            row => (1, row as usize),
+        };
+        let [west, center, east] = self.tiles[tile_row].map(|tile| tile[row]);
+
+        ((center << 1) | (west >> 63), center, (center >> 1) | (east << 63))
+    }
+}
+
+impl TileState {
+    fn tile(&self, key: Cell) -> &Tile {
+        self.tiles.get(&key).unwrap_or(&EMPTY_TILE)
+    }
+
+    fn neighborhood(&self, key: Cell) -> Neighborhood<'_> {
+        let tile = |x: i32, y: i32| self.tile((wrap_tile(key.0 + x), wrap_tile(key.1 + y)));
+        Neighborhood {
+            tiles: [
+                [tile(-1, -1), tile(0, -1), tile(1, -1)],
+                [tile(-1, 0), tile(0, 0), tile(1, 0)],
+                [tile(-1, 1), tile(0, 1), tile(1, 1)],
+            ],
+        }
+    }
+
+    // Tiles with live cells, and the tiles next to their edges with live
+    // cells, which are the only ones where cells can be born.
+    fn candidates(&self) -> HashSet<Cell> {
+        let mut candidates = HashSet::default();
+
+        for (key, tile) in self.tiles.iter() {
+            let west = tile.iter().any(|row| row & 1 != 0);
+            let east = tile.iter().any(|row| row >> 63 != 0);
+            let south = tile[0] != 0;
+            let north = tile[TILE_SIZE - 1] != 0;
+
+            let sides = [
+                (-1, -1, tile[0] & 1 != 0),
+                (0, -1, south),
+                (1, -1, tile[0] >> 63 != 0),
+                (-1, 0, west),
+                (0, 0, true),
+                (1, 0, east),
+                (-1, 1, tile[TILE_SIZE - 1] & 1 != 0),
+                (0, 1, north),
+                (1, 1, tile[TILE_SIZE - 1] >> 63 != 0),
+            ];
+            for (x, y, _) in sides.into_iter().filter(|side| side.2) {
+                candidates.insert((wrap_tile(key.0 + x), wrap_tile(key.1 + y)));
+            }
+        }
+
+        candidates
+    }
+
+    // Computes the next generation of the tile.
+    fn next_tile(&self, key: Cell) -> Tile {
+        let neighborhood = self.neighborhood(key);
+        let mut next = EMPTY_TILE;
+
+        for (row, next_row) in next.iter_mut().enumerate() {
+            let (south_west, south, south_east) = neighborhood.row(row as isize - 1);
+            let (west, alive, east) = neighborhood.row(row as isize);
+            let (north_west, north, north_east) = neighborhood.row(row as isize + 1);
+
+            let count = count_bits([south_west, south, south_east, west, east, north_west, north, north_east]);
+            for amount in 0..=8 {
+                if self.rule.born(amount) {
+                    *next_row |= count_is(count, amount) & !alive;
+                }
+                if self.rule.survives(amount) {
+                    *next_row |= count_is(count, amount) & alive;
+                }
+            }
+        }
+
+        next
+    }
+
+    fn set_cell(&mut self, cell: Cell, alive: bool) {
+        let (key, x, y) = tile_of(cell);
+        match alive {
+            true => self.tiles.entry(key).or_insert(EMPTY_TILE)[y] |= 1 << x,
+            _ => {
+                let Some(tile) = self.tiles.get_mut(&key) else {
+                    return;
+                };
+                tile[y] &= !(1 << x);
+                if tile.iter().all(|row| *row == 0) {
+                    self.tiles.remove(&key);
+                }
+            }
+        }
+    }
+
+    // Calls the function with every live cell in the tiles overlapping the
+    // rectangle, corners included.
+    fn for_each_in_rect(&self, min: Cell, max: Cell, mut function: impl FnMut(Cell)) {
+        for (key, tile) in self.tiles.iter() {
+            let origin = tile_origin(*key);
+            let (left, bottom) = (origin.0 as i64, origin.1 as i64);
+            let (right, top) = (left + TILE_SIZE as i64 - 1, bottom + TILE_SIZE as i64 - 1);
+            if right < min.0 as i64 || left > max.0 as i64 || top < min.1 as i64 || bottom > max.1 as i64 {
+                continue;
+            }
+
+            for_each_cell(*key, tile, &mut function);
+        }
+    }
+}
+
+fn for_each_cell(key: Cell, tile: &Tile, function: &mut impl FnMut(Cell)) {
+    let origin = tile_origin(key);
+    for (y, row) in tile.iter().enumerate() {
+        let mut row = *row;
+        while row != 0 {
+            let x = row.trailing_zeros();
+            function((origin.0 + x as i32, origin.1 + y as i32));
+            row &= row - 1;
+        }
+    }
+}
+
+impl State for TileState {
+    fn tick(&mut self) {
+        if !self.tiles.is_empty() {
+            self.generation += 1;
+        }
+
+        // The changes are kept around after ticking, as the changes of the tick.
+        self.births.clear();
+        self.deaths.clear();
+
+        let mut next_tiles = HashMap::default();
+        for key in self.candidates() {
+            let next = self.next_tile(key);
+            let current = *self.tile(key);
+            let origin = tile_origin(key);
+
+            for (y, (current_row, next_row)) in current.iter().zip(next.iter()).enumerate() {
+                let mut changed = current_row ^ next_row;
+                while changed != 0 {
+                    let x = changed.trailing_zeros();
+                    let cell = (origin.0 + x as i32, origin.1 + y as i32);
+                    match next_row & (1 << x) {
+                        0 => self.deaths.push(cell),
+                        _ => self.births.push(cell),
+                    }
+                    changed &= changed - 1;
+                }
+            }
+
+            if next.iter().any(|row| *row != 0) {
+                next_tiles.insert(key, next);
+            }
+        }
+        self.tiles = next_tiles;
+
+        if let Some(ages) = self.ages.as_mut() {
+            ages.update(self.generation, &self.births, &self.deaths);
+        }
+        if let Some(activity) = self.activity.as_mut() {
+            activity.update(&self.births, &self.deaths);
+        }
+    }
+
+    fn last_changes(&self) -> Option<Changes<'_>> {
+        Some(Changes {
+            births: &self.births,
+            deaths: &self.deaths,
+        })
+    }
+
+    fn insert_cells(&mut self, mut collection: Vec<Cell>) {
+        for cell in collection.drain(0..) {
+            self.insert_cell(cell);
+        }
+    }
+
+    fn insert_cells_rel(&mut self, mut collection: Vec<Cell>, view: (f64, f64)) {
+        for cell in collection.drain(0..) {
+            self.insert_cell(relative_to_view(cell, view));
+        }
+    }
+
+    fn insert_cell(&mut self, cell: Cell) {
+        self.set_cell(cell, true);
+        if let Some(ages) = self.ages.as_mut() {
+            ages.insert(self.generation, cell);
+        }
+    }
+
+    fn remove_cell(&mut self, cell: Cell) {
+        self.set_cell(cell, false);
+        if let Some(ages) = self.ages.as_mut() {
+            ages.remove(&cell);
+        }
+    }
+
+    fn toggle_cell(&mut self, cell: Cell) {
+        match self.is_alive(cell) {
+            true => self.remove_cell(cell),
+            _ => self.insert_cell(cell),
+        }
+    }
+
+    fn is_alive(&self, cell: Cell) -> bool {
+        let (key, x, y) = tile_of(cell);
+        self.tile(key)[y] & (1 << x) != 0
+    }
+
+    fn remove_cells(&mut self, mut collection: Vec<Cell>) {
+        for cell in collection.drain(0..) {
+            self.remove_cell(cell);
+        }
+    }
+
+    fn collect_cells(&self) -> Vec<Cell> {
+        let mut collection = Vec::with_capacity(self.count_cells());
+
+        for (key, tile) in self.tiles.iter() {
+            for_each_cell(*key, tile, &mut |cell| collection.push(cell));
+        }
+
+        collection
+    }
+
+    fn cells_in_rect(&self, min: Cell, max: Cell) -> Vec<Cell> {
+        let mut collection = Vec::default();
+        self.for_each_in_rect(min, max, |cell| {
+            if in_rect(&cell, min, max) {
+                collection.push(cell);
+            }
+        });
+
+        collection
+    }
+
+    fn count_cells(&self) -> usize {
+        self.tiles
+            .values()
+            .flat_map(|tile| tile.iter())
+            .map(|row| row.count_ones() as usize)
+            .sum()
+    }
+
+    fn random_cell(&self) -> Cell {
+        let mut rng = rand::thread_rng();
+        let (key, tile) = self.tiles.iter().nth(rng.gen_range(0..self.tiles.len())).unwrap();
+
+        let mut cells = Vec::new();
+        for_each_cell(*key, tile, &mut |cell| cells.push(cell));
+        cells[rng.gen_range(0..cells.len())]
+    }
+
+    fn generation(&self) -> usize {
+        self.generation
+    }
+
+    fn set_generation(&mut self, generation: usize) {
+        self.generation = generation;
+    }
+
+    fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+    }
+
+    fn topology(&self) -> Topology {
+        Topology::Unbounded
+    }
+
+    // Tiles do not line up with the edges of bounded universes, so those are
+    // left to the other engines.
+    fn set_topology(&mut self, topology: Topology) -> Result<(), String> {
+        match topology {
+            Topology::Unbounded => Ok(()),
+            _ => Err(format!("the tiles engine only supports the unbounded universe, not '{}'", topology)),
+        }
+    }
+
+    fn set_age_tracking(&mut self, enabled: bool) {
+        self.ages = match enabled {
+            true => {
+                let mut ages = ages::cell_ages();
+                for cell in self.collect_cells() {
+                    ages.insert(self.generation, cell);
+                }
+                Some(ages)
+            }
+            _ => None,
+        };
+    }
+
+    fn ages(&self) -> Option<&CellAges> {
+        self.ages.as_ref()
+    }
+
+    fn set_activity_tracking(&mut self, enabled: bool) {
+        self.activity = enabled.then(activity::activity);
+    }
+
+    fn activity(&self) -> Option<&Activity> {
+        self.activity.as_ref()
+    }
+
+    #[cfg(feature = "gui")]
+    fn get_tris(
+        &self,
+        view: (f64, f64),
+        cell_color: nannou::prelude::rgb::Rgb,
+        screen_left: i32,
+        screen_right: i32,
+        screen_top: i32,
+        screen_bottom: i32
+    ) -> LinkedList<Tri<([f32; 3], nannou::prelude::rgb::Rgb)>> {
+        let mut tri_list = LinkedList::default();
+
+        self.for_each_in_rect((screen_left, screen_bottom), (screen_right, screen_top), |cell| {
+            if cell.0 > screen_left && cell.0 < screen_right && cell.1 > screen_bottom && cell.1 < screen_top {
+                let [first_tri, second_tri] = cell_tris(&cell, view, cell_color);
+
+                tri_list.push_front(first_tri);
+                tri_list.push_front(second_tri);
+            }
+        });
+
+        tri_list
+    }
+}
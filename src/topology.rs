@@ -0,0 +1,227 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::soup::parse_size;
+use crate::state::Cell;
+
+// The shape of the universe. Bounded universes are width by height cells,
+// centered on (0, 0).
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Topology {
+    // Every i32 coordinate, wrapping around at the edges of i32 like a donut.
+    #[default]
+    Unbounded,
+    // Cells beyond the borders are always dead.
+    Plane { width: u32, height: u32 },
+    // Leaving on one side comes back in on the opposite side.
+    Torus { width: u32, height: u32 },
+    // Like a torus, but coming back in on the top or bottom mirrors left and
+    // right.
+    KleinBottle { width: u32, height: u32 },
+    // Coming back in on any side mirrors the other direction.
+    CrossSurface { width: u32, height: u32 },
+}
+
+impl Topology {
+    pub fn size(&self) -> Option<(u32, u32)> {
+        match *self {
+            Topology::Unbounded => None,
+            Topology::Plane { width, height }
+            | Topology::Torus { width, height }
+            | Topology::KleinBottle { width, height }
+            | Topology::CrossSurface { width, height } => Some((width, height)),
+        }
+    }
+
+    // Returns the lower left and upper right cells of bounded universes.
+    pub fn bounds(&self) -> Option<(Cell, Cell)> {
+        self.size().map(|(width, height)| {
+            let (left, bottom) = (-((width / 2) as i64), -((height / 2) as i64));
+            (
+                (left as i32, bottom as i32),
+                ((left + width as i64 - 1) as i32, (bottom + height as i64 - 1) as i32),
+            )
+        })
+    }
+
+    pub fn contains(&self, cell: &Cell) -> bool {
+        match self.bounds() {
+            Some((min, max)) => cell.0 >= min.0 && cell.0 <= max.0 && cell.1 >= min.1 && cell.1 <= max.1,
+            None => true,
+        }
+    }
+
+    // Returns where the cell ends up after wrapping around the universe, or
+    // None if it falls off the edge of a plane.
+    pub fn wrap(&self, cell: Cell) -> Option<Cell> {
+        // Whether crossing the left or right edge mirrors top and bottom, and
+        // whether crossing the top or bottom edge mirrors left and right.
+        let (mirror_vertically, mirror_horizontally) = match *self {
+            Topology::Unbounded => return Some(cell),
+            Topology::Plane { .. } => return self.contains(&cell).then_some(cell),
+            Topology::Torus { .. } => (false, false),
+            Topology::KleinBottle { .. } => (false, true),
+            Topology::CrossSurface { .. } => (true, true),
+        };
+
+        let ((left, bottom), _) = self.bounds().unwrap();
+        let (width, height) = self.size().unwrap();
+        let (width, height) = (width as i64, height as i64);
+        let (x, y) = (cell.0 as i64 - left as i64, cell.1 as i64 - bottom as i64);
+
+        let (mut wrapped_x, mut wrapped_y) = (x.rem_euclid(width), y.rem_euclid(height));
+        if mirror_vertically && x.div_euclid(width) % 2 != 0 {
+            wrapped_y = height - 1 - wrapped_y;
+        }
+        if mirror_horizontally && y.div_euclid(height) % 2 != 0 {
+            wrapped_x = width - 1 - wrapped_x;
+        }
+
+        Some(((wrapped_x + left as i64) as i32, (wrapped_y + bottom as i64) as i32))
+    }
+
+    // Same as wrap, for points anywhere within the cells, like the center of
+    // the view. Planes are left as they are.
+    pub fn wrap_point(&self, point: (f64, f64)) -> (f64, f64) {
+        let (mirror_vertically, mirror_horizontally) = match *self {
+            Topology::Unbounded | Topology::Plane { .. } => return point,
+            Topology::Torus { .. } => (false, false),
+            Topology::KleinBottle { .. } => (false, true),
+            Topology::CrossSurface { .. } => (true, true),
+        };
+
+        // Cells are centered on their coordinates.
+        let ((left, bottom), _) = self.bounds().unwrap();
+        let (left, bottom) = (left as f64 - 0.5, bottom as f64 - 0.5);
+        let (width, height) = self.size().unwrap();
+        let (width, height) = (width as f64, height as f64);
+        let (x, y) = (point.0 - left, point.1 - bottom);
+
+        let (mut wrapped_x, mut wrapped_y) = (x.rem_euclid(width), y.rem_euclid(height));
+        if mirror_vertically && x.div_euclid(width) % 2.0 != 0.0 {
+            wrapped_y = height - wrapped_y;
+        }
+        if mirror_horizontally && y.div_euclid(height) % 2.0 != 0.0 {
+            wrapped_x = width - wrapped_x;
+        }
+
+        (wrapped_x + left, wrapped_y + bottom)
+    }
+}
+
+// Parses "unbounded", or the name of a bounded universe followed by its size,
+// like "torus:100x80".
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(topology: &str) -> Result<Self, Self::Err> {
+        let topology = topology.trim();
+        if topology.eq_ignore_ascii_case("unbounded") {
+            return Ok(Topology::Unbounded);
+        }
+
+        let (name, size) = topology
+            .split_once(':')
+            .ok_or(format!("topology '{}' should be 'unbounded', or written as NAME:WIDTHxHEIGHT", topology))?;
+        let (width, height) = parse_size(size)?;
+        if width == 0 || height == 0 {
+            return Err(format!("size '{}' of a bounded universe must not be empty", size));
+        }
+
+        match name.to_ascii_lowercase().as_str() {
+            "plane" => Ok(Topology::Plane { width, height }),
+            "torus" => Ok(Topology::Torus { width, height }),
+            "klein" => Ok(Topology::KleinBottle { width, height }),
+            "cross" => Ok(Topology::CrossSurface { width, height }),
+            _ => Err(format!("unknown topology '{}', expected plane, torus, klein or cross", name)),
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Topology::Unbounded => return write!(f, "unbounded"),
+            Topology::Plane { .. } => "plane",
+            Topology::Torus { .. } => "torus",
+            Topology::KleinBottle { .. } => "klein",
+            Topology::CrossSurface { .. } => "cross",
+        };
+        let (width, height) = self.size().unwrap();
+        write!(f, "{}:{}x{}", name, width, height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unbounded_keeps_cells() {
+        for cell in [(0, 0), (i32::MAX, i32::MIN), (-7, 12)] {
+            assert_eq!(Topology::Unbounded.wrap(cell), Some(cell));
+        }
+    }
+
+    #[test]
+    fn plane_drops_cells_beyond_edges() {
+        let plane = Topology::Plane { width: 10, height: 8 };
+        assert_eq!(plane.wrap((-5, -4)), Some((-5, -4)));
+        assert_eq!(plane.wrap((4, 3)), Some((4, 3)));
+        assert_eq!(plane.wrap((5, 0)), None);
+        assert_eq!(plane.wrap((0, -5)), None);
+    }
+
+    #[test]
+    fn torus_wraps_to_opposite_side() {
+        let torus = Topology::Torus { width: 10, height: 8 };
+        assert_eq!(torus.wrap((5, 0)), Some((-5, 0)));
+        assert_eq!(torus.wrap((-6, 3)), Some((4, 3)));
+        assert_eq!(torus.wrap((2, 4)), Some((2, -4)));
+        assert_eq!(torus.wrap((25, -21)), Some((-5, 3)));
+    }
+
+    #[test]
+    fn klein_bottle_mirrors_across_top_and_bottom() {
+        let klein = Topology::KleinBottle { width: 10, height: 8 };
+        assert_eq!(klein.wrap((5, 0)), Some((-5, 0)));
+        assert_eq!(klein.wrap((2, 4)), Some((-3, -4)));
+        assert_eq!(klein.wrap((2, 12)), Some((2, -4)));
+    }
+
+    #[test]
+    fn cross_surface_mirrors_across_every_edge() {
+        let cross = Topology::CrossSurface { width: 10, height: 8 };
+        assert_eq!(cross.wrap((5, 1)), Some((-5, -2)));
+        assert_eq!(cross.wrap((2, 4)), Some((-3, -4)));
+    }
+
+    #[test]
+    fn wrapped_cells_stay_inside() {
+        let size = (7, 5);
+        for topology in [
+            Topology::Torus { width: size.0, height: size.1 },
+            Topology::KleinBottle { width: size.0, height: size.1 },
+            Topology::CrossSurface { width: size.0, height: size.1 },
+        ] {
+            for x in -30..30 {
+                for y in -30..30 {
+                    let wrapped = topology.wrap((x, y)).unwrap();
+                    assert!(topology.contains(&wrapped));
+                    if topology.contains(&(x, y)) {
+                        assert_eq!(wrapped, (x, y));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parses_and_displays() {
+        for text in ["unbounded", "plane:10x8", "torus:100x80", "klein:3x4", "cross:1x1"] {
+            assert_eq!(text.parse::<Topology>().unwrap().to_string(), text);
+        }
+        assert!("torus:0x8".parse::<Topology>().is_err());
+        assert!("sphere:10x8".parse::<Topology>().is_err());
+        assert!("torus".parse::<Topology>().is_err());
+    }
+}